@@ -1,254 +1,836 @@
-extern crate leveldb;
-
-use std::env;
-use std::fs::File;
+use std::fmt::Write as _;
+use std::fs;
 use std::io;
-use std::io::Read;
-use std::path::Path;
-
-use leveldb::database::Database;
-use leveldb::kv::KV;
-use leveldb::options::{Options, ReadOptions, WriteOptions};
-
-#[derive(Clone, Debug, PartialEq)]
-enum TagType {
-    End,
-    Byte,
-    Int32,
-    Int64,
-    Float,
-    String,
-    List,
-    Compound,
-}
-
-impl TagType {
-    fn parse<R: Read>(reader: &mut R) -> io::Result<Self> {
-        let mut type_buf = [0; 1];
-        reader.read_exact(&mut type_buf)?;
-        let tag_type_byte = type_buf[0];
-        let tag_type = match tag_type_byte {
-            0 => TagType::End,
-            1 => TagType::Byte,
-            3 => TagType::Int32,
-            4 => TagType::Int64,
-            5 => TagType::Float,
-            8 => TagType::String,
-            9 => TagType::List,
-            10 => TagType::Compound,
-            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid tag type: {}", tag_type_byte))),
-        };
-        Ok(tag_type)
-	}
-}
-
-#[derive(Debug)]
-enum Choice {
-    Byte(u8),
-    Int32(i32),
-    Int64(i64),
-    Float32(f32),
-    String(String),
-    List(TagType, Vec<Choice>),
-    Vec(Vec<Tag>),
-}
-
-impl Choice {
-    fn parse<R: Read>(reader: &mut R, tag_type: TagType) -> io::Result<Self> {
-        match tag_type {
-            TagType::End => Err(io::Error::new(io::ErrorKind::InvalidData, "Cannot parse value of End tag")),
-            TagType::Byte => {
-                let mut byte_value_buf = [0; 1];
-                reader.read_exact(&mut byte_value_buf)?;
-                Ok(Choice::Byte(byte_value_buf[0]))
-            }
-            TagType::Int32 => {
-                let mut int32_value_buf = [0; 4];
-                reader.read_exact(&mut int32_value_buf)?;
-                Ok(Choice::Int32(i32::from_le_bytes(int32_value_buf)))
-            }
-            TagType::Int64 => {
-                let mut int64_value_buf = [0; 8];
-                reader.read_exact(&mut int64_value_buf)?;
-                Ok(Choice::Int64(i64::from_le_bytes(int64_value_buf)))
-            }
-            TagType::Float => {
-                let mut float_value_buf = [0; 4];
-                reader.read_exact(&mut float_value_buf)?;
-                let float_value = f32::from_le_bytes(float_value_buf);
-                Ok(Choice::Float32(float_value))
-            }
-            TagType::String => {
-                let mut length_buf = [0; 2];
-                reader.read_exact(&mut length_buf)?;
-                let length = u16::from_le_bytes(length_buf) as usize;
-                let mut string_value_buf = vec![0; length];
-                reader.read_exact(&mut string_value_buf)?;
-                Ok(Choice::String(String::from_utf8_lossy(&string_value_buf).into_owned()))
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+#[cfg(feature = "leveldb")]
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "leveldb")]
+use std::io::Write as _;
+
+#[cfg(feature = "leveldb")]
+use minecraft_rust::block::BlockState;
+use minecraft_rust::chunk::subchunk::SUB_CHUNK_SIZE;
+#[cfg(feature = "leveldb")]
+use minecraft_rust::db::{self, ChunkRecord, DbKey, KeyCategory};
+#[cfg(feature = "leveldb")]
+use minecraft_rust::entity::Entity;
+use minecraft_rust::level::LevelData;
+#[cfg(feature = "json")]
+use minecraft_rust::nbt::JsonFormat;
+use minecraft_rust::nbt::{Choice, Diagnostic, ParseMode, SnbtFormat, Tag, TagType};
+#[cfg(feature = "leveldb")]
+use minecraft_rust::player::Player;
+use minecraft_rust::world::Dimension;
+use minecraft_rust::Error;
+#[cfg(feature = "leveldb")]
+use minecraft_rust::world::World;
+
+/// Minecraft Bedrock world analyzer.
+///
+/// Exits with 0 on success, 1 when reading or writing the world fails, 2 on
+/// invalid arguments and 3 when the requested record does not exist.
+#[derive(Parser)]
+#[command(name = "main", version)]
+struct Cli {
+    /// World directory, holding `level.dat` and the `db` folder
+    #[arg(long, short, global = true)]
+    world: Option<PathBuf>,
+
+    /// Output format
+    #[arg(long, short, global = true, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Keep every NBT type in JSON output, so it can be converted back to
+    /// binary NBT
+    #[arg(long, global = true)]
+    typed: bool,
+
+    /// Read corrupt NBT up to the first error instead of failing, and report
+    /// what was lost on standard error
+    #[arg(long, global = true)]
+    lenient: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+    Snbt,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Summarize the level data and the database
+    Info,
+    /// Read or edit NBT files
+    #[command(subcommand)]
+    Nbt(NbtCommand),
+    /// Inspect raw database records
+    #[command(subcommand)]
+    Db(DbCommand),
+    /// Show the subchunks, block entities and entities of a chunk
+    Chunk {
+        /// Chunk X coordinate
+        #[arg(allow_negative_numbers = true)]
+        x: i32,
+        /// Chunk Z coordinate
+        #[arg(allow_negative_numbers = true)]
+        z: i32,
+        #[command(flatten)]
+        dimension: DimensionArg,
+    },
+    /// List the players
+    Players,
+    /// List the entities
+    Entities,
+    /// Export the level data and the players
+    Export {
+        /// File to write instead of the standard output
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Render a top-down heightmap of a dimension as a PPM image
+    Render {
+        /// Image file to write
+        #[arg(long, short)]
+        output: PathBuf,
+        #[command(flatten)]
+        dimension: DimensionArg,
+    },
+}
+
+#[derive(Subcommand)]
+enum NbtCommand {
+    /// Print an NBT file, `level.dat` of the world by default
+    Dump {
+        /// NBT file, with or without a `level.dat` header
+        file: Option<PathBuf>,
+    },
+    /// Set a value in `level.dat`
+    Edit {
+        /// Dot-separated key path from the root compound, such as
+        /// `abilities.flySpeed`
+        path: String,
+        /// New value in SNBT, such as `0.1f` or `"My World"`
+        value: String,
+        /// Allow changing the type of an existing value
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbCommand {
+    /// List the keys of the database
+    Keys {
+        /// Only list keys of this category, such as `player` or `actor`
+        #[arg(long)]
+        category: Option<String>,
+    },
+    /// Print the value of a record
+    Get {
+        /// Key, as text such as `~local_player`
+        key: String,
+        /// Read the key as hexadecimal bytes
+        #[arg(long)]
+        hex: bool,
+    },
+}
+
+#[derive(Args)]
+struct DimensionArg {
+    /// Dimension: overworld, nether or end
+    #[arg(long, short, value_parser = parse_dimension, default_value = "overworld")]
+    dimension: Dimension,
+}
+
+fn parse_dimension(name: &str) -> Result<Dimension, String> {
+    Dimension::ALL.into_iter()
+        .find(|dimension| format!("{:?}", dimension).eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("unknown dimension '{}'", name))
+}
+
+/// A failed command, with the exit code it maps to.
+enum Failure {
+    Error(Error),
+    Usage(String),
+    NotFound(String),
+}
+
+impl Failure {
+    fn exit_code(&self) -> u8 {
+        match self {
+            Failure::Error(_) => 1,
+            Failure::Usage(_) => 2,
+            Failure::NotFound(_) => 3,
+        }
+    }
+}
+
+impl From<Error> for Failure {
+    fn from(err: Error) -> Self {
+        Failure::Error(err)
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(err: io::Error) -> Self {
+        Failure::Error(err.into())
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            match &failure {
+                Failure::Error(err) => eprintln!("error: {}", err),
+                Failure::Usage(message) | Failure::NotFound(message) => eprintln!("error: {}", message),
             }
-            TagType::List => {
-                let element_type = TagType::parse(reader)?;
-                let mut length_buf = [0; 4];
-                reader.read_exact(&mut length_buf)?;
-                let length = u32::from_le_bytes(length_buf) as usize;
-                let mut values = Vec::with_capacity(length);
-                for _ in 0..length {
-                    let element = Self::parse(reader, element_type.clone())?;
-                    values.push(element);
+            ExitCode::from(failure.exit_code())
+        }
+    }
+}
+
+fn run(cli: &Cli) -> Result<(), Failure> {
+    match &cli.command {
+        Command::Info => info(cli),
+        Command::Nbt(NbtCommand::Dump { file }) => nbt_dump(cli, file.as_ref()),
+        Command::Nbt(NbtCommand::Edit { path, value, force }) => nbt_edit(cli, path, value, *force),
+        Command::Db(DbCommand::Keys { category }) => db_keys(cli, category.as_deref()),
+        Command::Db(DbCommand::Get { key, hex }) => db_get(cli, key, *hex),
+        Command::Chunk { x, z, dimension } => chunk(cli, *x, *z, dimension.dimension),
+        Command::Players => players(cli),
+        Command::Entities => entities(cli),
+        Command::Export { output } => export(cli, output.as_ref()),
+        Command::Render { output, dimension } => render(cli, output, dimension.dimension),
+    }
+}
+
+fn world_dir(cli: &Cli) -> Result<&str, Failure> {
+    let world = cli.world.as_ref().ok_or_else(|| Failure::Usage("--world is required for this command".to_string()))?;
+    world.to_str().ok_or_else(|| Failure::Usage(format!("World path is not valid UTF-8: {}", world.display())))
+}
+
+#[cfg(feature = "leveldb")]
+fn open_world(cli: &Cli) -> Result<World, Failure> {
+    Ok(World::open(world_dir(cli)?)?)
+}
+
+#[cfg(not(feature = "leveldb"))]
+fn requires_leveldb() -> Result<(), Failure> {
+    Err(Failure::Usage("This command reads the world database, which needs the leveldb feature".to_string()))
+}
+
+fn compound(entries: Vec<(&str, Choice)>) -> Choice {
+    Choice::Vec(entries.into_iter().map(|(key, choice_value)| Tag::new(key, choice_value)).collect())
+}
+
+fn list(tag_type: TagType, values: Vec<Choice>) -> Choice {
+    let tag_type = if values.is_empty() { TagType::End } else { tag_type };
+    Choice::List(tag_type, values)
+}
+
+/// Adds an entry only when the value is known.
+fn push_optional(entries: &mut Vec<(&'static str, Choice)>, key: &'static str, choice_value: Option<Choice>) {
+    if let Some(choice_value) = choice_value {
+        entries.push((key, choice_value));
+    }
+}
+
+#[cfg(feature = "leveldb")]
+fn floats(values: &[f32]) -> Choice {
+    list(TagType::Float, values.iter().map(|&value| Choice::Float32(value)).collect())
+}
+
+fn render_output(cli: &Cli, choice_value: &Choice) -> Result<String, Failure> {
+    match cli.format {
+        Format::Text => {
+            let mut text = String::new();
+            write_text(&mut text, choice_value, 0);
+            Ok(text)
+        }
+        Format::Snbt => Ok(choice_value.to_snbt(SnbtFormat::Pretty) + "\n"),
+        #[cfg(feature = "json")]
+        Format::Json => {
+            let format = if cli.typed { JsonFormat::Typed } else { JsonFormat::Plain };
+            let json = serde_json::to_string_pretty(&choice_value.to_json(format)).map_err(io::Error::other)?;
+            Ok(json + "\n")
+        }
+        #[cfg(not(feature = "json"))]
+        Format::Json => Err(Failure::Usage("JSON output needs the json feature".to_string())),
+    }
+}
+
+fn print_output(cli: &Cli, choice_value: &Choice) -> Result<(), Failure> {
+    print!("{}", render_output(cli, choice_value)?);
+    Ok(())
+}
+
+fn is_nested(choice_value: &Choice) -> bool {
+    match choice_value {
+        Choice::Vec(compound_tags) => !compound_tags.is_empty(),
+        Choice::List(_, values) => values.iter().any(|value| matches!(value, Choice::Vec(_) | Choice::List(..))),
+        _ => false,
+    }
+}
+
+fn scalar_text(choice_value: &Choice) -> String {
+    fn join<T: ToString>(values: &[T]) -> String {
+        values.iter().map(T::to_string).collect::<Vec<_>>().join(", ")
+    }
+    match choice_value {
+        Choice::Byte(value) => value.to_string(),
+        Choice::Int16(value) => value.to_string(),
+        Choice::Int32(value) => value.to_string(),
+        Choice::Int64(value) => value.to_string(),
+        Choice::Float32(value) => value.to_string(),
+        Choice::Float64(value) => value.to_string(),
+        Choice::String(value) => value.clone(),
+        Choice::ByteArray(values) => join(values),
+        Choice::IntArray(values) => join(values),
+        Choice::LongArray(values) => join(values),
+        Choice::List(_, values) => values.iter().map(scalar_text).collect::<Vec<_>>().join(", "),
+        Choice::Vec(_) => String::new(),
+    }
+}
+
+/// Writes one `key: value` line per entry, indenting nested compounds and
+/// lists. A top-level list of plain values is written one value per line.
+fn write_text(out: &mut String, choice_value: &Choice, depth: usize) {
+    let indent = "  ".repeat(depth);
+    match choice_value {
+        Choice::Vec(compound_tags) => {
+            for child_tag in compound_tags {
+                let Some(child_value) = &child_tag.choice_value else { continue };
+                if is_nested(child_value) {
+                    writeln!(out, "{}{}:", indent, child_tag.key).unwrap();
+                    write_text(out, child_value, depth + 1);
+                } else {
+                    writeln!(out, "{}{}: {}", indent, child_tag.key, scalar_text(child_value)).unwrap();
                 }
-                Ok(Choice::List(element_type, values))
             }
-            TagType::Compound => {
-                let mut compound_tags = Vec::new();
-                loop {
-                    match Tag::parse(reader) {
-                        Ok(child_tag) => {
-                            if child_tag.tag_type == TagType::End {
-                                break;
-                            }
-                            compound_tags.push(child_tag);
-                        }
-                        Err(err) => {
-                            eprintln!("Error parsing child tag: {}", err);
-                            return Err(err);
-                        }
-                    }
-                }
-                Ok(Choice::Vec(compound_tags))
+        }
+        Choice::List(_, values) if is_nested(choice_value) => {
+            for (index, value) in values.iter().enumerate() {
+                writeln!(out, "{}[{}]:", indent, index).unwrap();
+                write_text(out, value, depth + 1);
             }
         }
+        Choice::List(_, values) if depth == 0 => {
+            for value in values {
+                writeln!(out, "{}", scalar_text(value)).unwrap();
+            }
+        }
+        _ => writeln!(out, "{}{}", indent, scalar_text(choice_value)).unwrap(),
     }
 }
 
-#[derive(Debug)]
-struct Tag {
-    tag_type: TagType,
-    key: String,
-    choice_value: Option<Choice>,
+fn level_summary(level_data: &LevelData) -> Choice {
+    let mut entries = vec![("version", Choice::Int32(level_data.version))];
+    push_optional(&mut entries, "name", level_data.level_name().map(|name| Choice::String(name.to_string())));
+    push_optional(&mut entries, "seed", level_data.random_seed().map(Choice::Int64));
+    if let (Some(x), Some(y), Some(z)) = (level_data.spawn_x(), level_data.spawn_y(), level_data.spawn_z()) {
+        entries.push(("spawn", list(TagType::Int32, vec![Choice::Int32(x), Choice::Int32(y), Choice::Int32(z)])));
+    }
+    push_optional(&mut entries, "game_type", level_data.game_type().map(|game_type| Choice::String(format!("{:?}", game_type))));
+    push_optional(&mut entries, "difficulty", level_data.difficulty().map(|difficulty| Choice::String(format!("{:?}", difficulty))));
+    push_optional(&mut entries, "time", level_data.time().map(Choice::Int64));
+    push_optional(&mut entries, "last_played", level_data.last_played().map(Choice::Int64));
+    push_optional(&mut entries, "storage_version", level_data.storage_version().map(Choice::Int32));
+    compound(entries)
 }
 
-impl Tag {
-    fn typed_parse<R: Read>(reader: &mut R, key: String, tag_type: TagType) -> io::Result<Self> {
-        let mut tag = Tag {
-            tag_type: tag_type.clone(),
-            key,
-            choice_value: Some(Choice::parse(reader, tag_type)?),
-        };
+fn parse_mode(cli: &Cli) -> ParseMode {
+    if cli.lenient {
+        ParseMode::Lenient
+    } else {
+        ParseMode::Strict
+    }
+}
 
-        //println!("{} ({:?}): {:?}", tag.key, tag_type, tag.choice_value);
+fn warn(diagnostics: impl IntoIterator<Item = Diagnostic>) {
+    for diagnostic in diagnostics {
+        eprintln!("Warning: {}", diagnostic);
+    }
+}
+
+/// Reads `level.dat` in the parse mode chosen on the command line.
+fn read_level_data(cli: &Cli) -> Result<LevelData, Failure> {
+    let (level_data, diagnostics) = LevelData::from_file_with(world_dir(cli)?, parse_mode(cli))?;
+    warn(diagnostics);
+    Ok(level_data)
+}
+
+fn info(cli: &Cli) -> Result<(), Failure> {
+    let level_data = read_level_data(cli)?;
+    #[cfg_attr(not(feature = "leveldb"), allow(unused_mut))]
+    let mut entries = vec![("level", level_summary(&level_data))];
+
+    #[cfg(feature = "leveldb")]
+    {
+        let world = open_world(cli)?;
+
+        // Count the entries of each kind
+        let mut categories: BTreeMap<KeyCategory, (usize, usize)> = BTreeMap::new();
+        let mut chunk_records: BTreeMap<String, i32> = BTreeMap::new();
+        let mut chunks = BTreeSet::new();
+        for (key, value) in db::entries(world.database()) {
+            let (count, size) = categories.entry(key.category()).or_default();
+            *count += 1;
+            *size += value.len();
+            if let DbKey::Chunk(chunk_key) = key {
+                let record = match chunk_key.record {
+                    ChunkRecord::SubChunkPrefix(_) => "SubChunkPrefix".to_string(),
+                    record => format!("{:?}", record),
+                };
+                *chunk_records.entry(record).or_default() += 1;
+                chunks.insert((chunk_key.dimension, chunk_key.x, chunk_key.z));
+            }
+        }
 
-        Ok(tag)
+        let categories = categories.into_iter()
+            .map(|(category, (count, size))| Tag::new(format!("{:?}", category), compound(vec![
+                ("entries", Choice::Int64(count as i64)),
+                ("bytes", Choice::Int64(size as i64)),
+            ])))
+            .collect();
+        let chunk_records = chunk_records.into_iter()
+            .map(|(record, count)| Tag::new(record, Choice::Int32(count)))
+            .collect();
+        entries.push(("database", Choice::Vec(categories)));
+        entries.push(("chunks", Choice::Int64(chunks.len() as i64)));
+        entries.push(("chunk_records", Choice::Vec(chunk_records)));
     }
 
-    fn parse<R: Read>(reader: &mut R) -> io::Result<Self> {
-        let tag_type = TagType::parse(reader)?;
+    print_output(cli, &compound(entries))
+}
 
-        if tag_type == TagType::End {
-            return Ok(Tag {
-                tag_type,
-                key: "".to_string(),
-                choice_value: None,
-            });
+/// Reads the tags of an NBT file, skipping the 8-byte header of `level.dat`
+/// files when there is one. A truncated file is still recognized by a buffer
+/// length beyond the end of the file and the root compound after the header.
+fn read_nbt_file(bytes: &[u8], mode: ParseMode) -> Result<(Vec<Tag>, Option<Diagnostic>), Error> {
+    if bytes.len() >= 8 {
+        let buffer_length = i32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        let matches = match usize::try_from(buffer_length) {
+            Ok(buffer_length) if buffer_length == bytes.len() - 8 => true,
+            Ok(buffer_length) => buffer_length > bytes.len() - 8 && bytes.get(8) == Some(&TagType::Compound.id()),
+            Err(_) => false,
+        };
+        if matches {
+            return Tag::parse_all_with(&bytes[8..], 8, mode);
         }
+    }
+    Tag::parse_all_with(bytes, 0, mode)
+}
 
-        let mut key_length_buf = [0; 2];
-        reader.read_exact(&mut key_length_buf)?;
-        let key_length = u16::from_le_bytes(key_length_buf) as usize;
+fn nbt_dump(cli: &Cli, file: Option<&PathBuf>) -> Result<(), Failure> {
+    let path = match file {
+        Some(file) => file.clone(),
+        None => PathBuf::from(world_dir(cli)?).join("level.dat"),
+    };
+    let bytes = fs::read(&path).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => Failure::NotFound(format!("No such file: {}", path.display())),
+        kind => Failure::from(io::Error::new(kind, format!("{}: {}", path.display(), err))),
+    })?;
+    let (tags, diagnostic) = read_nbt_file(&bytes, parse_mode(cli))?;
+    warn(diagnostic);
+    for tag in tags {
+        if let Some(choice_value) = &tag.choice_value {
+            print_output(cli, choice_value)?;
+        }
+    }
+    Ok(())
+}
 
-        let mut key_buf = vec![0; key_length];
-        reader.read_exact(&mut key_buf)?;
-        let key = String::from_utf8_lossy(&key_buf).into_owned();
+/// Splits a dot-separated key path, checking that every key but the last
+/// names a compound where it exists.
+fn edit_path<'p>(level_data: &LevelData, path: &'p str) -> Result<Vec<&'p str>, Failure> {
+    let keys: Vec<&str> = path.split('.').collect();
+    if keys.iter().any(|key| key.is_empty()) {
+        return Err(Failure::Usage(format!("Invalid key path '{}': keys cannot be empty", path)));
+    }
+    for end in 1..keys.len() {
+        if let Some(parent) = level_data.value(&keys[..end]) {
+            if parent.as_compound().is_none() {
+                return Err(Failure::Usage(format!(
+                    "{} holds a {:?}, not a compound", keys[..end].join("."), parent.tag_type(),
+                )));
+            }
+        }
+    }
+    Ok(keys)
+}
 
-        Self::typed_parse(reader, key, tag_type)
+fn nbt_edit(cli: &Cli, path: &str, value: &str, force: bool) -> Result<(), Failure> {
+    let world_dir = world_dir(cli)?;
+    let choice_value = Choice::from_snbt(value).map_err(|err| Failure::Usage(format!("Invalid value: {}", err)))?;
+
+    let mut level_data = LevelData::from_file(world_dir)?;
+    let path = edit_path(&level_data, path)?;
+    if let Some(current) = level_data.value(&path) {
+        if current.tag_type() != choice_value.tag_type() && !force {
+            return Err(Failure::Usage(format!(
+                "{} holds a {:?} but the new value is a {:?}; pass --force to change its type",
+                path.join("."), current.tag_type(), choice_value.tag_type(),
+            )));
+        }
     }
+    level_data.set_value(&path, choice_value)?;
+    level_data.save(world_dir)?;
+    Ok(())
 }
 
-#[derive(Debug)]
-struct LevelData {
-    version: i32,
-    buffer_length: i32,
-    tags: Vec<Tag>
+#[cfg(feature = "leveldb")]
+fn db_keys(cli: &Cli, category: Option<&str>) -> Result<(), Failure> {
+    let category = category.map(|name| {
+        [
+            KeyCategory::ChunkRecord, KeyCategory::Player, KeyCategory::Map, KeyCategory::Village, KeyCategory::Structure,
+            KeyCategory::Actor, KeyCategory::ActorDigest, KeyCategory::Global, KeyCategory::Unknown,
+        ].into_iter()
+            .find(|category| format!("{:?}", category).eq_ignore_ascii_case(name))
+            .ok_or_else(|| Failure::Usage(format!("Unknown key category: {}", name)))
+    }).transpose()?;
+
+    let world = open_world(cli)?;
+    let keys = db::keys(world.database())
+        .filter(|key| category.is_none_or(|category| key.category() == category))
+        .map(|key| Choice::String(key.to_string()))
+        .collect();
+    print_output(cli, &list(TagType::String, keys))
 }
 
-impl LevelData {
-    fn from_file(world_dir: &str) -> io::Result<Self> {
-        // Construct file path
-        let file_path = format!("{}/level.dat", world_dir);
+#[cfg(not(feature = "leveldb"))]
+fn db_keys(_cli: &Cli, _category: Option<&str>) -> Result<(), Failure> {
+    requires_leveldb()
+}
 
-        // Open the file in read-only mode
-        let mut file = File::open(&file_path)?;
+#[cfg(feature = "leveldb")]
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let text = text.strip_prefix("0x").unwrap_or(text);
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok()).collect()
+}
 
-        // Read the version
-        let mut version_buffer = [0; 4];
-        file.read_exact(&mut version_buffer)?;
-        let version = i32::from_le_bytes(version_buffer);
+#[cfg(feature = "leveldb")]
+fn db_get(cli: &Cli, key: &str, hex: bool) -> Result<(), Failure> {
+    let key_bytes = if hex {
+        parse_hex(key).ok_or_else(|| Failure::Usage(format!("Invalid hexadecimal key: {}", key)))?
+    } else {
+        key.as_bytes().to_vec()
+    };
+    let key = DbKey::parse(&key_bytes);
 
-        // Read the buffer length
-        let mut buffer_length_buffer = [0; 4];
-        file.read_exact(&mut buffer_length_buffer)?;
-        let buffer_length = i32::from_le_bytes(buffer_length_buffer);
+    let world = open_world(cli)?;
+    let value = db::get(world.database(), &key)?.ok_or_else(|| Failure::NotFound(format!("No record for key {}", key)))?;
 
-        // Read the buffer
-        let mut tags = Vec::new();
-        while let Ok(tag) = Tag::parse(&mut file) {
-            if tag.tag_type == TagType::End {
-                break;
+    // Values are mostly NBT; anything else is printed as hexadecimal
+    match Tag::parse_all(&value) {
+        Ok(tags) if !tags.is_empty() => {
+            for tag in &tags {
+                if let Some(choice_value) = &tag.choice_value {
+                    print_output(cli, choice_value)?;
+                }
             }
-            tags.push(tag);
         }
+        _ => {
+            let hex: String = value.iter().map(|byte| format!("{:02x}", byte)).collect();
+            print_output(cli, &Choice::String(hex))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "leveldb"))]
+fn db_get(_cli: &Cli, _key: &str, _hex: bool) -> Result<(), Failure> {
+    requires_leveldb()
+}
+
+#[cfg(feature = "leveldb")]
+fn chunk(cli: &Cli, x: i32, z: i32, dimension: Dimension) -> Result<(), Failure> {
+    let world = open_world(cli)?;
+    let world_dimension = world.dimension(dimension);
+    if !world_dimension.contains_chunk(x, z)? {
+        return Err(Failure::NotFound(format!("No chunk at {}, {} in the {:?}", x, z, dimension)));
+    }
 
-        Ok(LevelData {
-            version,
-            buffer_length,
-            tags,
-        })
+    let size = SUB_CHUNK_SIZE as i32;
+    let y_range = world_dimension.y_range();
+    let mut sub_chunks = Vec::new();
+    for y_index in y_range.start.div_euclid(size)..y_range.end.div_euclid(size) {
+        let Some(sub_chunk) = world_dimension.sub_chunk(x, z, y_index as i8)? else { continue };
+        let palette = sub_chunk.layers.first()
+            .map(|layer| layer.palette.iter()
+                .map(|tag| Choice::String(BlockState::from_tag(tag).map_or_else(|_| "?".to_string(), |state| state.to_string())))
+                .collect())
+            .unwrap_or_default();
+        sub_chunks.push(compound(vec![
+            ("y", Choice::Int32(y_index * size)),
+            ("version", Choice::Byte(sub_chunk.version as i8)),
+            ("layers", Choice::Int32(sub_chunk.layers.len() as i32)),
+            ("palette", list(TagType::String, palette)),
+        ]));
     }
 
-    fn print(&self) {
-        println!("Version: {}", self.version);
-        println!("Buffer Length: {}", self.buffer_length);
-        println!("Tags: {:?}", self.tags);
+    let (block_entities, errors) = world_dimension.block_entities(x, z)?;
+    for err in errors {
+        eprintln!("Warning: skipped block entity: {}", err);
     }
+    let block_entities = block_entities.iter()
+        .map(|block_entity| compound(vec![
+            ("id", Choice::String(block_entity.id.clone())),
+            ("position", list(TagType::Int32, vec![Choice::Int32(block_entity.x), Choice::Int32(block_entity.y), Choice::Int32(block_entity.z)])),
+        ]))
+        .collect();
+    let entities = world_dimension.entities(x, z)?.iter().map(entity_summary).collect();
+
+    print_output(cli, &compound(vec![
+        ("x", Choice::Int32(x)),
+        ("z", Choice::Int32(z)),
+        ("dimension", Choice::String(format!("{:?}", dimension))),
+        ("sub_chunks", list(TagType::Compound, sub_chunks)),
+        ("block_entities", list(TagType::Compound, block_entities)),
+        ("entities", list(TagType::Compound, entities)),
+    ]))
 }
 
-fn main() -> io::Result<()> {
-    // Parse command-line arguments
-    let args: Vec<String> = env::args().collect();
+#[cfg(not(feature = "leveldb"))]
+fn chunk(_cli: &Cli, _x: i32, _z: i32, _dimension: Dimension) -> Result<(), Failure> {
+    requires_leveldb()
+}
 
-    // Check if --world_dir argument is provided
-    if args.len() < 3 {
-        eprintln!("Usage: {} --world_dir <world_directory>", args[0]);
-        std::process::exit(1);
-    }
+#[cfg(feature = "leveldb")]
+fn player_summary(key: &DbKey, player: &Player) -> Choice {
+    let mut entries = vec![("key", Choice::String(key.to_string()))];
+    push_optional(&mut entries, "position", player.position.as_ref().map(|position| floats(position)));
+    push_optional(&mut entries, "dimension", player.dimension.and_then(Dimension::from_id).map(|dimension| Choice::String(format!("{:?}", dimension))));
+    push_optional(&mut entries, "xp_level", player.xp_level.map(Choice::Int32));
+    compound(entries)
+}
 
-    // Extract world directory from command-line arguments
-    let world_dir = &args[2];
+#[cfg(feature = "leveldb")]
+fn players(cli: &Cli) -> Result<(), Failure> {
+    let world = open_world(cli)?;
+    let players = db::players(world.database())?.iter()
+        .map(|(key, player)| player_summary(key, player))
+        .collect();
+    print_output(cli, &list(TagType::Compound, players))
+}
 
-    // Read level data from the file
-    let level_data = LevelData::from_file(world_dir)?;
+#[cfg(not(feature = "leveldb"))]
+fn players(_cli: &Cli) -> Result<(), Failure> {
+    requires_leveldb()
+}
+
+#[cfg(feature = "leveldb")]
+fn entity_summary(entity: &Entity) -> Choice {
+    let mut entries = vec![("identifier", Choice::String(entity.identifier.clone()))];
+    push_optional(&mut entries, "unique_id", entity.unique_id.map(Choice::Int64));
+    push_optional(&mut entries, "position", entity.position.as_ref().map(|position| floats(position)));
+    push_optional(&mut entries, "health", entity.health.map(Choice::Float32));
+    push_optional(&mut entries, "custom_name", entity.custom_name.clone().map(Choice::String));
+    compound(entries)
+}
+
+#[cfg(feature = "leveldb")]
+fn entities(cli: &Cli) -> Result<(), Failure> {
+    let world = open_world(cli)?;
+    let entities = db::entities(world.database())
+        .map(|entity| entity.map(|entity| entity_summary(&entity)))
+        .collect::<Result<Vec<Choice>, Error>>()?;
+    print_output(cli, &list(TagType::Compound, entities))
+}
 
-    // Print the level data
-    println!("Level Data:");
-    level_data.print();
+#[cfg(not(feature = "leveldb"))]
+fn entities(_cli: &Cli) -> Result<(), Failure> {
+    requires_leveldb()
+}
+
+fn export(cli: &Cli, output: Option<&PathBuf>) -> Result<(), Failure> {
+    let level_data = read_level_data(cli)?;
+    let root = level_data.root().and_then(|tag| tag.choice_value.clone()).unwrap_or(Choice::Vec(Vec::new()));
+    #[cfg_attr(not(feature = "leveldb"), allow(unused_mut))]
+    let mut entries = vec![
+        ("version", Choice::Int32(level_data.version)),
+        ("level", root),
+    ];
+
+    #[cfg(feature = "leveldb")]
+    {
+        let world = open_world(cli)?;
+        let players = db::players(world.database())?.into_iter()
+            .map(|(key, player)| compound(vec![
+                ("key", Choice::String(key.to_string())),
+                ("data", player.tag.choice_value.unwrap_or(Choice::Vec(Vec::new()))),
+            ]))
+            .collect();
+        entries.push(("players", list(TagType::Compound, players)));
+    }
 
-    let mut options = Options::new();
-    options.block_size = Some(4096);
-    let levelDbPath = Path::new(world_dir).join("db");
-    let mut database: Database<i32> = match Database::open(levelDbPath.as_ref(), options) {
-        Ok(db) => { db },
-        Err(e) => { panic!("failed to open database: {:?}", e) }
+    let rendered = render_output(cli, &compound(entries))?;
+    match output {
+        Some(output) => fs::write(output, rendered)?,
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+/// Largest image `render` writes, in pixels.
+const MAX_RENDER_PIXELS: usize = 8192 * 8192;
+
+/// Returns the width and height of the image covering the chunks between two
+/// corners, one pixel per column.
+#[cfg_attr(not(feature = "leveldb"), allow(dead_code))]
+fn render_size((min_x, min_z): (i32, i32), (max_x, max_z): (i32, i32)) -> Result<(usize, usize), Failure> {
+    let side = |min: i32, max: i32| {
+        let chunks = max.checked_sub(min)?.checked_add(1)?;
+        usize::try_from(chunks).ok()?.checked_mul(SUB_CHUNK_SIZE)
     };
+    match (side(min_x, max_x), side(min_z, max_z)) {
+        (Some(width), Some(height)) if width.checked_mul(height).is_some_and(|pixels| pixels <= MAX_RENDER_PIXELS) => Ok((width, height)),
+        _ => Err(Failure::Usage(format!(
+            "Chunks {}, {} to {}, {} need an image larger than {} pixels",
+            min_x, min_z, max_x, max_z, MAX_RENDER_PIXELS,
+        ))),
+    }
+}
 
-    let read_opts = ReadOptions::new();
-    let res = database.get(read_opts, 1);
+#[cfg(feature = "leveldb")]
+fn render(cli: &Cli, output: &PathBuf, dimension: Dimension) -> Result<(), Failure> {
+    let world = open_world(cli)?;
+    let world_dimension = world.dimension(dimension);
 
-    match res {
-      Ok(data) => {
-        assert!(data.is_some());
-        assert_eq!(data, Some(vec![1]));
-      }
-      Err(e) => { panic!("failed reading data: {:?}", e) }
+    let chunks: BTreeSet<(i32, i32)> = world_dimension.chunks().collect();
+    let (Some(min_x), Some(max_x)) = (chunks.iter().map(|&(x, _)| x).min(), chunks.iter().map(|&(x, _)| x).max()) else {
+        return Err(Failure::NotFound(format!("No chunks in the {:?}", dimension)));
+    };
+    let min_z = chunks.iter().map(|&(_, z)| z).min().unwrap();
+    let max_z = chunks.iter().map(|&(_, z)| z).max().unwrap();
+
+    // One pixel per column, shaded by height; missing chunks stay black
+    let (width, height) = render_size((min_x, min_z), (max_x, max_z))?;
+    let y_span = (dimension.y_range().end - dimension.y_range().start) as f32;
+    let mut pixels = vec![0u8; width * height * 3];
+    for &(chunk_x, chunk_z) in &chunks {
+        let Some(column_meta) = world_dimension.column_meta(chunk_x, chunk_z)? else { continue };
+        for z in 0..SUB_CHUNK_SIZE {
+            for x in 0..SUB_CHUNK_SIZE {
+                let Some(column_height) = column_meta.height(x, z) else { continue };
+                let shade = (column_height as f32 / y_span * 255.0).clamp(0.0, 255.0) as u8;
+                let pixel_x = (chunk_x - min_x) as usize * SUB_CHUNK_SIZE + x;
+                let pixel_z = (chunk_z - min_z) as usize * SUB_CHUNK_SIZE + z;
+                let offset = (pixel_z * width + pixel_x) * 3;
+                pixels[offset..offset + 3].fill(shade);
+            }
+        }
     }
 
+    let mut image = Vec::new();
+    write!(image, "P6\n{} {}\n255\n", width, height)?;
+    image.extend_from_slice(&pixels);
+    fs::write(output, image)?;
     Ok(())
 }
+
+#[cfg(not(feature = "leveldb"))]
+fn render(_cli: &Cli, _output: &PathBuf, _dimension: Dimension) -> Result<(), Failure> {
+    requires_leveldb()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level_data() -> LevelData {
+        let root = compound(vec![
+            ("LevelName", Choice::String("World".to_string())),
+            ("abilities", compound(vec![("flySpeed", Choice::Float32(0.05))])),
+        ]);
+        LevelData {
+            version: 10,
+            buffer_length: 0,
+            tags: vec![Tag::new("", root)],
+        }
+    }
+
+    #[test]
+    fn parse_arguments() {
+        let cli = Cli::try_parse_from(["main", "-w", "world", "nbt", "edit", "abilities.flySpeed", "0.1f", "--force"]).unwrap();
+        assert_eq!(cli.world, Some(PathBuf::from("world")));
+        assert!(matches!(&cli.command, Command::Nbt(NbtCommand::Edit { path, value, force: true })
+            if path == "abilities.flySpeed" && value == "0.1f"));
+
+        // Global options are accepted after the subcommand
+        let cli = Cli::try_parse_from(["main", "chunk", "-3", "5", "--dimension", "nether", "-f", "json", "--typed", "--lenient"]).unwrap();
+        assert!(matches!(cli.command, Command::Chunk { x: -3, z: 5, dimension: DimensionArg { dimension: Dimension::Nether } }));
+        assert!(cli.format == Format::Json && cli.typed && cli.lenient);
+
+        let cli = Cli::try_parse_from(["main", "db", "get", "~local_player"]).unwrap();
+        assert!(matches!(&cli.command, Command::Db(DbCommand::Get { key, hex: false }) if key == "~local_player"));
+        assert!(cli.format == Format::Text && cli.world.is_none());
+
+        for args in [
+            &["main"][..],
+            &["main", "chunk", "1", "2", "--dimension", "moon"],
+            &["main", "render"],
+            &["main", "-f", "xml", "info"],
+            &["main", "nbt", "edit", "LevelName"],
+        ] {
+            let err = Cli::try_parse_from(args).err().unwrap();
+            assert_eq!(err.exit_code(), 2, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(Failure::from(Error::UnexpectedEof).exit_code(), 1);
+        assert_eq!(Failure::from(io::Error::other("disk")).exit_code(), 1);
+        assert_eq!(Failure::Usage(String::new()).exit_code(), 2);
+        assert_eq!(Failure::NotFound(String::new()).exit_code(), 3);
+
+        // Commands reading the world need --world
+        let cli = Cli::try_parse_from(["main", "info"]).unwrap();
+        assert_eq!(run(&cli).err().map(|failure| failure.exit_code()), Some(2));
+        let cli = Cli::try_parse_from(["main", "nbt", "dump", "/nonexistent/level.dat"]).unwrap();
+        assert_eq!(run(&cli).err().map(|failure| failure.exit_code()), Some(3));
+    }
+
+    #[test]
+    fn edit_paths() {
+        let level_data = level_data();
+        assert_eq!(edit_path(&level_data, "abilities.flySpeed").ok(), Some(vec!["abilities", "flySpeed"]));
+        assert_eq!(edit_path(&level_data, "abilities.mayfly").ok(), Some(vec!["abilities", "mayfly"]));
+        assert_eq!(edit_path(&level_data, "experiments.data_driven_items").ok(), Some(vec!["experiments", "data_driven_items"]));
+
+        for path in ["", "abilities.", ".LevelName", "LevelName.x", "abilities.flySpeed.x.y"] {
+            match edit_path(&level_data, path) {
+                Err(failure) => assert_eq!(failure.exit_code(), 2, "{}", path),
+                Ok(keys) => panic!("{} accepted as {:?}", path, keys),
+            }
+        }
+    }
+
+    #[test]
+    fn render_sizes() {
+        assert_eq!(render_size((0, 0), (0, 0)).ok(), Some((16, 16)));
+        assert_eq!(render_size((-3, 5), (2, 5)).ok(), Some((96, 16)));
+        assert_eq!(render_size((-256, -256), (255, 255)).ok(), Some((8192, 8192)));
+
+        for (min, max) in [((0, 0), (10_000, 10_000)), ((0, 0), (512, 511)), ((i32::MIN, 0), (i32::MAX, 0)), ((0, i32::MIN), (0, i32::MAX))] {
+            match render_size(min, max) {
+                Err(failure) => assert_eq!(failure.exit_code(), 2),
+                Ok(size) => panic!("{:?} to {:?} accepted as {:?}", min, max, size),
+            }
+        }
+    }
+}
@@ -0,0 +1,265 @@
+pub mod legacy;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::nbt::{Choice, Tag};
+
+/// Block state version written for blocks created from a name or a string.
+pub const BLOCK_STATE_VERSION: i32 = 18_090_528;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StateValue {
+    Byte(i8),
+    Int(i32),
+    String(String),
+}
+
+impl StateValue {
+    fn from_choice(choice_value: &Choice) -> Option<Self> {
+        match choice_value {
+            Choice::Byte(value) => Some(StateValue::Byte(*value)),
+            Choice::Int32(value) => Some(StateValue::Int(*value)),
+            Choice::String(value) => Some(StateValue::String(value.clone())),
+            _ => None,
+        }
+    }
+
+    fn to_choice(&self) -> Choice {
+        match self {
+            StateValue::Byte(value) => Choice::Byte(*value),
+            StateValue::Int(value) => Choice::Int32(*value),
+            StateValue::String(value) => Choice::String(value.clone()),
+        }
+    }
+
+    fn parse(text: &str) -> Self {
+        if let Some(quoted) = text.strip_prefix('"').and_then(|text| text.strip_suffix('"')) {
+            return StateValue::String(quoted.to_string());
+        }
+        match text {
+            "true" => return StateValue::Byte(1),
+            "false" => return StateValue::Byte(0),
+            _ => {}
+        }
+        if let Some(byte) = text.strip_suffix('b').and_then(|byte| byte.parse().ok()) {
+            return StateValue::Byte(byte);
+        }
+        match text.parse() {
+            Ok(value) => StateValue::Int(value),
+            Err(_) => StateValue::String(text.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for StateValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateValue::Byte(0) => write!(f, "false"),
+            StateValue::Byte(1) => write!(f, "true"),
+            StateValue::Byte(value) => write!(f, "{}b", value),
+            StateValue::Int(value) => write!(f, "{}", value),
+            // Quote the strings that would otherwise read back as another value
+            StateValue::String(value) => {
+                let ambiguous = StateValue::parse(value) != StateValue::String(value.clone())
+                    || value.is_empty()
+                    || value.contains([',', '=', '[', ']', '"']);
+                if ambiguous {
+                    write!(f, "\"{}\"", value)
+                } else {
+                    write!(f, "{}", value)
+                }
+            }
+        }
+    }
+}
+
+/// A block state from a subchunk palette: a name such as `minecraft:oak_log`,
+/// its states and the version it was saved with. Equality and hashing ignore
+/// the version so that the same block matches across game versions.
+#[derive(Clone, Debug)]
+pub struct BlockState {
+    pub name: String,
+    pub states: BTreeMap<String, StateValue>,
+    pub version: Option<i32>,
+}
+
+impl BlockState {
+    pub fn new(name: &str) -> Self {
+        BlockState {
+            name: name.to_string(),
+            states: BTreeMap::new(),
+            version: Some(BLOCK_STATE_VERSION),
+        }
+    }
+
+    pub fn with_state(mut self, key: &str, value: StateValue) -> Self {
+        self.states.insert(key.to_string(), value);
+        self
+    }
+
+    pub fn from_tag(tag: &Tag) -> Result<Self> {
+        let name = tag.get("name")
+            .and_then(|name| name.choice_value.as_ref()?.as_str())
+            .ok_or_else(|| Error::invalid_data("Block state without a name"))?
+            .to_string();
+
+        // Palettes saved before block states had a data value instead
+        if tag.get("states").is_none() {
+            if let Some(data) = tag.get("val").and_then(|val| val.choice_value.as_ref()?.as_i16()) {
+                if let Some(block_state) = legacy::upgrade_named(&name, data as u8) {
+                    return Ok(block_state);
+                }
+            }
+        }
+
+        let mut states = BTreeMap::new();
+        if let Some(states_tag) = tag.get("states") {
+            let compound_tags = states_tag.choice_value.as_ref()
+                .and_then(Choice::as_compound)
+                .ok_or_else(|| Error::invalid_data(format!("Block {} states are not a compound", name)))?;
+            for state_tag in compound_tags {
+                let value = state_tag.choice_value.as_ref()
+                    .and_then(StateValue::from_choice)
+                    .ok_or_else(|| Error::invalid_data(format!("Invalid value for state {} of block {}", state_tag.key, name)))?;
+                states.insert(state_tag.key.clone(), value);
+            }
+        }
+
+        let version = tag.get("version").and_then(|version| version.choice_value.as_ref()?.as_i32());
+
+        Ok(BlockState {
+            name,
+            states,
+            version,
+        })
+    }
+
+    /// Builds the palette compound for this block state.
+    pub fn to_tag(&self) -> Tag {
+        let states = self.states.iter()
+            .map(|(key, value)| Tag::new(key.as_str(), value.to_choice()))
+            .collect();
+        let mut compound_tags = vec![
+            Tag::new("name", Choice::String(self.name.clone())),
+            Tag::new("states", Choice::Vec(states)),
+        ];
+        if let Some(version) = self.version {
+            compound_tags.push(Tag::new("version", Choice::Int32(version)));
+        }
+        Tag::new("", Choice::Vec(compound_tags))
+    }
+}
+
+impl PartialEq for BlockState {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.states == other.states
+    }
+}
+
+impl Eq for BlockState {}
+
+impl Hash for BlockState {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.states.hash(state);
+    }
+}
+
+impl fmt::Display for BlockState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.states.is_empty() {
+            write!(f, "[")?;
+            for (position, (key, value)) in self.states.iter().enumerate() {
+                if position > 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, "{}={}", key, value)?;
+            }
+            write!(f, "]")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for BlockState {
+    type Err = Error;
+
+    /// Parses the `name[key=value,...]` form, adding the `minecraft:`
+    /// namespace to names without one.
+    fn from_str(text: &str) -> Result<Self> {
+        let invalid = |message: &str| Error::invalid_input(format!("{}: {}", message, text));
+
+        let (name, states_text) = match text.split_once('[') {
+            Some((name, rest)) => (name, Some(rest.strip_suffix(']').ok_or_else(|| invalid("Missing ] in block state"))?)),
+            None => (text, None),
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(invalid("Missing block name"));
+        }
+        let mut block_state = if name.contains(':') {
+            BlockState::new(name)
+        } else {
+            BlockState::new(&format!("minecraft:{}", name))
+        };
+
+        if let Some(states_text) = states_text.filter(|states_text| !states_text.trim().is_empty()) {
+            for state_text in split_states(states_text) {
+                let (key, value) = state_text.split_once('=').ok_or_else(|| invalid("Missing = in block state"))?;
+                let key = key.trim().trim_matches('"');
+                if key.is_empty() {
+                    return Err(invalid("Missing state name"));
+                }
+                block_state.states.insert(key.to_string(), StateValue::parse(value.trim()));
+            }
+        }
+
+        Ok(block_state)
+    }
+}
+
+/// Splits the states on the commas outside quoted values.
+fn split_states(states_text: &str) -> Vec<&str> {
+    let mut states = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (position, character) in states_text.char_indices() {
+        match character {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                states.push(&states_text[start..position]);
+                start = position + 1;
+            }
+            _ => {}
+        }
+    }
+    states.push(&states_text[start..]);
+    states
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_form() {
+        let block_state = BlockState::new("minecraft:oak_log")
+            .with_state("pillar_axis", StateValue::String("y".to_string()));
+        assert_eq!(block_state.to_string(), "minecraft:oak_log[pillar_axis=y]");
+        assert_eq!("oak_log[pillar_axis=y]".parse::<BlockState>().unwrap(), block_state);
+
+        let block_state = BlockState::new("minecraft:stone_slab")
+            .with_state("top_slot_bit", StateValue::Byte(1))
+            .with_state("stone_slab_type", StateValue::String("true".to_string()))
+            .with_state("age", StateValue::Int(3));
+        let text = block_state.to_string();
+        assert_eq!(text, "minecraft:stone_slab[age=3,stone_slab_type=\"true\",top_slot_bit=true]");
+        assert_eq!(text.parse::<BlockState>().unwrap(), block_state);
+        assert_eq!(BlockState::from_tag(&block_state.to_tag()).unwrap(), block_state);
+    }
+}
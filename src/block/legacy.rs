@@ -0,0 +1,133 @@
+use crate::block::{BlockState, StateValue};
+
+/// Version given to the block states built from legacy ids. The names and
+/// states below are those of that version, which the game upgrades itself.
+pub const LEGACY_BLOCK_STATE_VERSION: i32 = 17_825_792;
+
+/// Block names by legacy numeric id.
+const LEGACY_NAMES: [&str; 256] = [
+    "air", "stone", "grass", "dirt", "cobblestone", "planks", "sapling", "bedrock",
+    "flowing_water", "water", "flowing_lava", "lava", "sand", "gravel", "gold_ore", "iron_ore",
+    "coal_ore", "log", "leaves", "sponge", "glass", "lapis_ore", "lapis_block", "dispenser",
+    "sandstone", "noteblock", "bed", "golden_rail", "detector_rail", "sticky_piston", "web", "tallgrass",
+    "deadbush", "piston", "pistonArmCollision", "wool", "element_0", "yellow_flower", "red_flower", "brown_mushroom",
+    "red_mushroom", "gold_block", "iron_block", "double_stone_slab", "stone_slab", "brick_block", "tnt", "bookshelf",
+    "mossy_cobblestone", "obsidian", "torch", "fire", "mob_spawner", "oak_stairs", "chest", "redstone_wire",
+    "diamond_ore", "diamond_block", "crafting_table", "wheat", "farmland", "furnace", "lit_furnace", "standing_sign",
+    "wooden_door", "ladder", "rail", "stone_stairs", "wall_sign", "lever", "stone_pressure_plate", "iron_door",
+    "wooden_pressure_plate", "redstone_ore", "lit_redstone_ore", "unlit_redstone_torch", "redstone_torch", "stone_button", "snow_layer", "ice",
+    "snow", "cactus", "clay", "reeds", "jukebox", "fence", "pumpkin", "netherrack",
+    "soul_sand", "glowstone", "portal", "lit_pumpkin", "cake", "unpowered_repeater", "powered_repeater", "invisibleBedrock",
+    "trapdoor", "monster_egg", "stonebrick", "brown_mushroom_block", "red_mushroom_block", "iron_bars", "glass_pane", "melon_block",
+    "pumpkin_stem", "melon_stem", "vine", "fence_gate", "brick_stairs", "stone_brick_stairs", "mycelium", "waterlily",
+    "nether_brick", "nether_brick_fence", "nether_brick_stairs", "nether_wart", "enchanting_table", "brewing_stand", "cauldron", "end_portal",
+    "end_portal_frame", "end_stone", "dragon_egg", "redstone_lamp", "lit_redstone_lamp", "dropper", "activator_rail", "cocoa",
+    "sandstone_stairs", "emerald_ore", "ender_chest", "tripwire_hook", "tripWire", "emerald_block", "spruce_stairs", "birch_stairs",
+    "jungle_stairs", "command_block", "beacon", "cobblestone_wall", "flower_pot", "carrots", "potatoes", "wooden_button",
+    "skull", "anvil", "trapped_chest", "light_weighted_pressure_plate", "heavy_weighted_pressure_plate", "unpowered_comparator", "powered_comparator", "daylight_detector",
+    "redstone_block", "quartz_ore", "hopper", "quartz_block", "quartz_stairs", "double_wooden_slab", "wooden_slab", "stained_hardened_clay",
+    "stained_glass_pane", "leaves2", "log2", "acacia_stairs", "dark_oak_stairs", "slime", "glow_stick", "iron_trapdoor",
+    "prismarine", "seaLantern", "hay_block", "carpet", "hardened_clay", "coal_block", "packed_ice", "double_plant",
+    "standing_banner", "wall_banner", "daylight_detector_inverted", "red_sandstone", "red_sandstone_stairs", "double_stone_slab2", "stone_slab2", "spruce_fence_gate",
+    "birch_fence_gate", "jungle_fence_gate", "dark_oak_fence_gate", "acacia_fence_gate", "repeating_command_block", "chain_command_block", "hard_glass_pane", "hard_stained_glass_pane",
+    "chemical_heat", "spruce_door", "birch_door", "jungle_door", "acacia_door", "dark_oak_door", "grass_path", "frame",
+    "chorus_flower", "purpur_block", "colored_torch_rg", "purpur_stairs", "colored_torch_bp", "undyed_shulker_box", "end_bricks", "frosted_ice",
+    "end_rod", "end_gateway", "allow", "deny", "border_block", "magma", "nether_wart_block", "red_nether_brick",
+    "bone_block", "structure_void", "shulker_box", "purple_glazed_terracotta", "white_glazed_terracotta", "orange_glazed_terracotta", "magenta_glazed_terracotta", "light_blue_glazed_terracotta",
+    "yellow_glazed_terracotta", "lime_glazed_terracotta", "pink_glazed_terracotta", "gray_glazed_terracotta", "silver_glazed_terracotta", "cyan_glazed_terracotta", "chalkboard", "blue_glazed_terracotta",
+    "brown_glazed_terracotta", "green_glazed_terracotta", "red_glazed_terracotta", "black_glazed_terracotta", "concrete", "concretePowder", "chemistry_table", "underwater_torch",
+    "chorus_plant", "stained_glass", "camera", "podzol", "beetroot", "stonecutter", "glowingobsidian", "netherreactor",
+    "info_update", "info_update2", "movingBlock", "observer", "structure_block", "hard_glass", "hard_stained_glass", "reserved6",
+];
+
+const COLORS: [&str; 16] = [
+    "white", "orange", "magenta", "light_blue", "yellow", "lime", "pink", "gray",
+    "silver", "cyan", "purple", "blue", "brown", "green", "red", "black",
+];
+
+const WOOD_TYPES: [&str; 6] = ["oak", "spruce", "birch", "jungle", "acacia", "dark_oak"];
+
+const PILLAR_AXES: [&str; 4] = ["y", "x", "z", "y"];
+
+/// Returns the legacy numeric id of a block name, with or without the
+/// `minecraft:` namespace.
+pub fn legacy_id(name: &str) -> Option<u8> {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    LEGACY_NAMES.iter().position(|legacy_name| *legacy_name == name).map(|id| id as u8)
+}
+
+/// Builds the block state of a legacy id and data value. Blocks whose data
+/// value is not mapped below keep only their name.
+pub fn upgrade(id: u8, data: u8) -> BlockState {
+    let data = data as usize;
+    let string = |value: &str| StateValue::String(value.to_string());
+    let bit = |mask: usize| StateValue::Byte((data & mask != 0) as i8);
+    let pick = |values: &[&str], index: usize| string(values.get(index).copied().unwrap_or(values[0]));
+
+    let states: Vec<(&str, StateValue)> = match LEGACY_NAMES[id as usize] {
+        "stone" => vec![("stone_type", pick(&["stone", "granite", "granite_smooth", "diorite", "diorite_smooth", "andesite", "andesite_smooth"], data))],
+        "dirt" => vec![("dirt_type", pick(&["normal", "coarse"], data))],
+        "planks" | "fence" | "double_wooden_slab" => vec![("wood_type", pick(&WOOD_TYPES, data & 7))],
+        "wooden_slab" => vec![("wood_type", pick(&WOOD_TYPES, data & 7)), ("top_slot_bit", bit(8))],
+        "sapling" => vec![("sapling_type", pick(&WOOD_TYPES, data & 7)), ("age_bit", bit(8))],
+        "log" => vec![("old_log_type", pick(&WOOD_TYPES[..4], data & 3)), ("pillar_axis", pick(&PILLAR_AXES, data >> 2 & 3))],
+        "log2" => vec![("new_log_type", pick(&WOOD_TYPES[4..], data & 1)), ("pillar_axis", pick(&PILLAR_AXES, data >> 2 & 3))],
+        "leaves" => vec![("old_leaf_type", pick(&WOOD_TYPES[..4], data & 3)), ("update_bit", bit(4)), ("persistent_bit", bit(8))],
+        "leaves2" => vec![("new_leaf_type", pick(&WOOD_TYPES[4..], data & 1)), ("update_bit", bit(4)), ("persistent_bit", bit(8))],
+        "sand" => vec![("sand_type", pick(&["normal", "red"], data))],
+        "sponge" => vec![("sponge_type", pick(&["dry", "wet"], data))],
+        "sandstone" | "red_sandstone" => vec![("sand_stone_type", pick(&["default", "heiroglyphs", "cut", "smooth"], data))],
+        "wool" | "carpet" | "stained_hardened_clay" | "stained_glass" | "stained_glass_pane" | "concrete" | "concretePowder" | "shulker_box" => {
+            vec![("color", pick(&COLORS, data & 15))]
+        }
+        "tallgrass" => vec![("tall_grass_type", pick(&["default", "tall", "fern", "snow"], data))],
+        "red_flower" => vec![("flower_type", pick(&["poppy", "orchid", "allium", "houstonia", "tulip_red", "tulip_orange", "tulip_white", "tulip_pink", "oxeye", "cornflower", "lily_of_the_valley"], data))],
+        "stone_slab" | "double_stone_slab" => vec![
+            ("stone_slab_type", pick(&["smooth_stone", "sandstone", "wood", "cobblestone", "brick", "stone_brick", "quartz", "nether_brick"], data & 7)),
+            ("top_slot_bit", bit(8)),
+        ],
+        "stonebrick" => vec![("stone_brick_type", pick(&["default", "mossy", "cracked", "chiseled", "smooth"], data))],
+        "quartz_block" | "purpur_block" => vec![("chisel_type", pick(&["default", "chiseled", "lines", "smooth"], data & 3)), ("pillar_axis", pick(&PILLAR_AXES, data >> 2 & 3))],
+        "hay_block" | "bone_block" => vec![("pillar_axis", pick(&PILLAR_AXES, data >> 2 & 3))],
+        "prismarine" => vec![("prismarine_block_type", pick(&["default", "dark", "bricks"], data))],
+        "cobblestone_wall" => vec![("wall_block_type", pick(&["cobblestone", "mossy_cobblestone"], data))],
+        "double_plant" => vec![("double_plant_type", pick(&["sunflower", "syringa", "grass", "fern", "rose", "paeonia"], data & 7)), ("upper_block_bit", bit(8))],
+        "flowing_water" | "water" | "flowing_lava" | "lava" => vec![("liquid_depth", StateValue::Int(data as i32))],
+        "wheat" | "carrots" | "potatoes" | "beetroot" | "pumpkin_stem" | "melon_stem" => vec![("growth", StateValue::Int(data as i32 & 7))],
+        "snow_layer" => vec![("height", StateValue::Int(data as i32 & 7)), ("covered_bit", bit(8))],
+        "farmland" => vec![("moisturized_amount", StateValue::Int(data as i32 & 7))],
+        "torch" | "redstone_torch" | "unlit_redstone_torch" | "underwater_torch" => {
+            vec![("torch_facing_direction", pick(&["unknown", "west", "east", "north", "south", "top"], data & 7))]
+        }
+        name if name.ends_with("_stairs") => vec![("weirdo_direction", StateValue::Int(data as i32 & 3)), ("upside_down_bit", bit(4))],
+        _ => Vec::new(),
+    };
+
+    let mut block_state = BlockState::new(&format!("minecraft:{}", LEGACY_NAMES[id as usize]));
+    block_state.version = Some(LEGACY_BLOCK_STATE_VERSION);
+    for (key, value) in states {
+        block_state = block_state.with_state(key, value);
+    }
+    block_state
+}
+
+/// Builds the block state of a legacy palette entry, which has a name and a
+/// data value instead of states.
+pub fn upgrade_named(name: &str, data: u8) -> Option<BlockState> {
+    Some(upgrade(legacy_id(name)?, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upgrade_legacy_ids() {
+        assert_eq!(upgrade(0, 0).to_string(), "minecraft:air");
+        assert_eq!(upgrade(17, 2 | 4).to_string(), "minecraft:log[old_log_type=birch,pillar_axis=x]");
+        assert_eq!(upgrade(35, 14).to_string(), "minecraft:wool[color=red]");
+        assert_eq!(upgrade(53, 5).to_string(), "minecraft:oak_stairs[upside_down_bit=true,weirdo_direction=1]");
+        assert_eq!(upgrade_named("minecraft:planks", 5).map(|block_state| block_state.to_string()), Some("minecraft:planks[wood_type=dark_oak]".to_string()));
+        assert_eq!(legacy_id("reserved6"), Some(255));
+    }
+}
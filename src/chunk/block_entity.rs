@@ -0,0 +1,201 @@
+use crate::error::{Error, Result};
+use crate::nbt::{Choice, Tag};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlockEntityKind {
+    /// Chests and the containers stored like them: ender chests, barrels and
+    /// shulker boxes.
+    Chest,
+    Sign,
+    Furnace,
+    MobSpawner,
+    Beehive,
+    CommandBlock,
+    Other,
+}
+
+impl BlockEntityKind {
+    pub fn from_id(id: &str) -> Self {
+        match id {
+            "Chest" | "EnderChest" | "Barrel" | "ShulkerBox" => BlockEntityKind::Chest,
+            "Sign" | "HangingSign" => BlockEntityKind::Sign,
+            "Furnace" | "BlastFurnace" | "Smoker" => BlockEntityKind::Furnace,
+            "MobSpawner" => BlockEntityKind::MobSpawner,
+            "Beehive" => BlockEntityKind::Beehive,
+            "CommandBlock" => BlockEntityKind::CommandBlock,
+            _ => BlockEntityKind::Other,
+        }
+    }
+}
+
+/// A block entity from the `BlockEntity` record of a chunk, with its absolute
+/// position and the full NBT compound it was read from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockEntity {
+    pub id: String,
+    pub kind: BlockEntityKind,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub tag: Tag,
+}
+
+impl BlockEntity {
+    pub fn from_tag(tag: Tag) -> Result<Self> {
+        let id = tag.get("id")
+            .and_then(|id| id.choice_value.as_ref()?.as_str())
+            .ok_or_else(|| Error::invalid_data("Block entity without an id"))?
+            .to_string();
+        let coordinate = |key: &str| {
+            tag.get(key)
+                .and_then(|coordinate| coordinate.choice_value.as_ref()?.as_i32())
+                .ok_or_else(|| Error::invalid_data(format!("Block entity {} without {}", id, key)))
+        };
+        let (x, y, z) = (coordinate("x")?, coordinate("y")?, coordinate("z")?);
+
+        Ok(BlockEntity {
+            kind: BlockEntityKind::from_id(&id),
+            id,
+            x,
+            y,
+            z,
+            tag,
+        })
+    }
+
+    /// Parses all the block entities of a `BlockEntity` record. Compounds
+    /// that are not valid block entities, such as ones without a position,
+    /// are skipped and their errors returned alongside.
+    pub fn parse_all(bytes: &[u8]) -> Result<(Vec<Self>, Vec<Error>)> {
+        let mut block_entities = Vec::new();
+        let mut errors = Vec::new();
+        for tag in Tag::parse_all(bytes)? {
+            match Self::from_tag(tag) {
+                Ok(block_entity) => block_entities.push(block_entity),
+                Err(err) => errors.push(err),
+            }
+        }
+        Ok((block_entities, errors))
+    }
+
+    /// Serializes block entities into a `BlockEntity` record.
+    pub fn write_all(block_entities: &[BlockEntity]) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        for block_entity in block_entities {
+            block_entity.tag.write(&mut bytes)?;
+        }
+        Ok(bytes)
+    }
+
+    fn value(&self, key: &str) -> Option<&Choice> {
+        self.tag.get(key)?.choice_value.as_ref()
+    }
+
+    pub fn custom_name(&self) -> Option<&str> {
+        self.value("CustomName")?.as_str()
+    }
+
+    /// Returns the item compounds of a container, such as a chest or a furnace.
+    pub fn items(&self) -> Option<&[Choice]> {
+        self.value("Items")?.as_list()
+    }
+
+    /// Returns the text on the front of a sign, falling back to the single
+    /// text of signs written before they had two sides.
+    pub fn sign_text(&self) -> Option<&str> {
+        match self.tag.get("FrontText") {
+            Some(front_text) => front_text.get("Text")?.choice_value.as_ref()?.as_str(),
+            None => self.value("Text")?.as_str(),
+        }
+    }
+
+    /// Returns the identifier of the entity a spawner spawns.
+    pub fn spawner_entity(&self) -> Option<&str> {
+        self.value("EntityIdentifier")?.as_str()
+    }
+
+    /// Returns the bees and other entities living in a beehive or a bee nest.
+    pub fn occupants(&self) -> Option<&[Choice]> {
+        self.value("Occupants")?.as_list()
+    }
+
+    pub fn command(&self) -> Option<&str> {
+        self.value("Command")?.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tests::push_name;
+
+    fn push_string(buf: &mut Vec<u8>, name: &str, value: &str) {
+        push_name(buf, 8, name);
+        buf.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        buf.extend_from_slice(value.as_bytes());
+    }
+
+    fn push_int(buf: &mut Vec<u8>, name: &str, value: i32) {
+        push_name(buf, 3, name);
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn push_position(buf: &mut Vec<u8>, x: i32, y: Option<i32>, z: i32) {
+        push_int(buf, "x", x);
+        if let Some(y) = y {
+            push_int(buf, "y", y);
+        }
+        push_int(buf, "z", z);
+    }
+
+    #[test]
+    fn parse_all() {
+        let mut record = Vec::new();
+
+        push_name(&mut record, 10, "");
+        push_string(&mut record, "id", "Chest");
+        push_position(&mut record, -5, Some(64), 17);
+        push_name(&mut record, 9, "Items");
+        record.push(10);
+        record.extend_from_slice(&1i32.to_le_bytes());
+        push_name(&mut record, 1, "Count");
+        record.push(3);
+        push_string(&mut record, "Name", "minecraft:apple");
+        record.push(0);
+        record.push(0);
+
+        push_name(&mut record, 10, "");
+        push_string(&mut record, "id", "Sign");
+        push_position(&mut record, 0, None, 0);
+        record.push(0);
+
+        push_name(&mut record, 10, "");
+        push_string(&mut record, "id", "HangingSign");
+        push_position(&mut record, 1, Some(-60), 2);
+        push_name(&mut record, 10, "FrontText");
+        push_string(&mut record, "Text", "Hello");
+        record.push(0);
+        record.push(0);
+
+        let (block_entities, errors) = BlockEntity::parse_all(&record).unwrap();
+        assert_eq!(block_entities.len(), 2);
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], Error::InvalidData(message) if message == "Block entity Sign without y"));
+
+        let chest = &block_entities[0];
+        assert_eq!((chest.kind.clone(), chest.x, chest.y, chest.z), (BlockEntityKind::Chest, -5, 64, 17));
+        let items = chest.items().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].as_compound().unwrap()[1].choice_value.as_ref().unwrap().as_str(), Some("minecraft:apple"));
+        assert_eq!(chest.sign_text(), None);
+
+        let sign = &block_entities[1];
+        assert_eq!((sign.kind.clone(), sign.id.as_str()), (BlockEntityKind::Sign, "HangingSign"));
+        assert_eq!(sign.sign_text(), Some("Hello"));
+
+        let written = BlockEntity::write_all(&block_entities).unwrap();
+        let (reparsed, errors) = BlockEntity::parse_all(&written).unwrap();
+        assert_eq!(reparsed, block_entities);
+        assert!(errors.is_empty());
+    }
+}
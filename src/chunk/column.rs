@@ -0,0 +1,263 @@
+use std::io::Read;
+use std::ops::Range;
+
+use crate::chunk::subchunk::{read_indices, BlockStorage, SUB_CHUNK_SIZE, SUB_CHUNK_VOLUME};
+use crate::error::{Error, Result};
+
+/// Number of columns in a chunk.
+const COLUMN_COUNT: usize = SUB_CHUNK_SIZE * SUB_CHUNK_SIZE;
+
+/// Header byte of a biome storage that repeats the one below it.
+const COPY_PREVIOUS_HEADER: u8 = 0xFF;
+
+/// The biomes of one subchunk: a palette of biome ids and, for each block,
+/// its index in the palette, in the same XZY order as the block storage.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BiomeStorage {
+    pub indices: Vec<u16>,
+    pub palette: Vec<i32>,
+}
+
+impl BiomeStorage {
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<i32> {
+        let palette_index = *self.indices.get(BlockStorage::checked_index(x, y, z)?)?;
+        self.palette.get(palette_index as usize).copied()
+    }
+
+    fn parse<R: Read>(reader: &mut R, header: u8) -> Result<Self> {
+        let bits_per_block = header >> 1;
+        if header & 1 != 0 {
+            return Err(Error::invalid_data("Runtime biome storage is not supported"));
+        }
+
+        // Unpack the palette indices from the words
+        let indices = read_indices(reader, bits_per_block)?;
+
+        // A single biome is stored without the palette length
+        let palette_length = if bits_per_block == 0 {
+            1
+        } else {
+            let mut palette_length_buf = [0; 4];
+            reader.read_exact(&mut palette_length_buf)?;
+            let palette_length = i32::from_le_bytes(palette_length_buf);
+            if palette_length < 0 {
+                return Err(Error::invalid_data(format!("Invalid palette length: {}", palette_length)));
+            }
+            palette_length
+        };
+
+        // Read the palette of biome ids
+        let mut palette = Vec::with_capacity((palette_length as usize).min(SUB_CHUNK_VOLUME));
+        for _ in 0..palette_length {
+            let mut biome_id_buf = [0; 4];
+            reader.read_exact(&mut biome_id_buf)?;
+            palette.push(i32::from_le_bytes(biome_id_buf));
+        }
+
+        Ok(BiomeStorage {
+            indices,
+            palette,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Biomes {
+    /// One biome id per column, from the legacy `Data2D` record.
+    Flat(Vec<u8>),
+    /// One biome storage per subchunk from the bottom of the dimension up,
+    /// from the `Data3D` record.
+    Sections { min_y: i32, sections: Vec<BiomeStorage> },
+}
+
+/// The per-column data of a chunk: its heightmap and biomes, stored under
+/// the `Data3D` key or, in legacy worlds, the `Data2D` key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChunkColumnMeta {
+    /// Heights as stored, indexed by `z * 16 + x`.
+    pub heightmap: Vec<i16>,
+    pub biomes: Biomes,
+}
+
+impl ChunkColumnMeta {
+    /// Parses a `Data3D` record. `y_range` is the range of Y coordinates of
+    /// the dimension: the first biome storage starts at its lowest one and
+    /// there is at most one storage per subchunk of it.
+    pub fn parse_data3d<R: Read>(reader: &mut R, y_range: Range<i32>) -> Result<Self> {
+        let heightmap = read_heightmap(reader)?;
+        let max_sections = usize::try_from(y_range.end.saturating_sub(y_range.start)).unwrap_or(0) / SUB_CHUNK_SIZE;
+
+        // Read biome storages until the end of the record
+        let mut sections: Vec<BiomeStorage> = Vec::new();
+        loop {
+            let mut header_buf = [0; 1];
+            if reader.read(&mut header_buf)? == 0 {
+                break;
+            }
+            if sections.len() == max_sections {
+                return Err(Error::invalid_data(format!("More than {} biome storages", max_sections)));
+            }
+            let section = if header_buf[0] == COPY_PREVIOUS_HEADER {
+                match sections.last() {
+                    Some(previous) => previous.clone(),
+                    None => return Err(Error::invalid_data("First biome storage repeats a previous one")),
+                }
+            } else {
+                BiomeStorage::parse(reader, header_buf[0])?
+            };
+            sections.push(section);
+        }
+
+        Ok(ChunkColumnMeta {
+            heightmap,
+            biomes: Biomes::Sections { min_y: y_range.start, sections },
+        })
+    }
+
+    /// Parses a legacy `Data2D` record.
+    pub fn parse_data2d<R: Read>(reader: &mut R) -> Result<Self> {
+        let heightmap = read_heightmap(reader)?;
+
+        let mut biomes_buf = vec![0; COLUMN_COUNT];
+        reader.read_exact(&mut biomes_buf)?;
+
+        Ok(ChunkColumnMeta {
+            heightmap,
+            biomes: Biomes::Flat(biomes_buf),
+        })
+    }
+
+    /// Returns the height of a column, with coordinates relative to the chunk.
+    pub fn height(&self, x: usize, z: usize) -> Option<i16> {
+        if x >= SUB_CHUNK_SIZE || z >= SUB_CHUNK_SIZE {
+            return None;
+        }
+        self.heightmap.get(z * SUB_CHUNK_SIZE + x).copied()
+    }
+
+    /// Returns the biome id at a position, with `x` and `z` relative to the
+    /// chunk and `y` absolute. Legacy records ignore `y`.
+    pub fn biome_id(&self, x: usize, y: i32, z: usize) -> Option<i32> {
+        if x >= SUB_CHUNK_SIZE || z >= SUB_CHUNK_SIZE {
+            return None;
+        }
+        match &self.biomes {
+            Biomes::Flat(biome_ids) => biome_ids.get(z * SUB_CHUNK_SIZE + x).map(|&biome_id| biome_id as i32),
+            Biomes::Sections { min_y, sections } => {
+                let offset = usize::try_from(y.checked_sub(*min_y)?).ok()?;
+                let section = sections.get(offset / SUB_CHUNK_SIZE)?;
+                section.get(x, offset % SUB_CHUNK_SIZE, z)
+            }
+        }
+    }
+}
+
+fn read_heightmap<R: Read>(reader: &mut R) -> Result<Vec<i16>> {
+    let mut heightmap_buf = [0; COLUMN_COUNT * 2];
+    reader.read_exact(&mut heightmap_buf)?;
+    Ok(heightmap_buf.chunks_exact(2)
+        .map(|height| i16::from_le_bytes([height[0], height[1]]))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A heightmap where each column's height is its index.
+    fn heightmap() -> Vec<u8> {
+        (0..COLUMN_COUNT as i16).flat_map(|height| height.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn parse_data3d() {
+        let mut bytes = heightmap();
+
+        // One bit per block, block (0, 1, 0) is desert and the rest is plains
+        bytes.push(1 << 1);
+        let mut words = vec![0u32; 128];
+        words[0] = 0b10;
+        for word in words {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes.extend_from_slice(&2i32.to_le_bytes());
+        bytes.extend_from_slice(&1i32.to_le_bytes());
+        bytes.extend_from_slice(&2i32.to_le_bytes());
+
+        // The second section repeats the first
+        bytes.push(COPY_PREVIOUS_HEADER);
+
+        // The third is a single biome, without words or palette length
+        bytes.push(0);
+        bytes.extend_from_slice(&7i32.to_le_bytes());
+
+        let meta = ChunkColumnMeta::parse_data3d(&mut Cursor::new(&bytes), -64..320).unwrap();
+        assert_eq!(meta.height(3, 2), Some(35));
+        assert_eq!(meta.height(16, 0), None);
+        let Biomes::Sections { min_y, sections } = &meta.biomes else { panic!("{:?}", meta.biomes) };
+        assert_eq!((*min_y, sections.len()), (-64, 3));
+        assert_eq!(sections[1], sections[0]);
+        assert_eq!(sections[2].palette, vec![7]);
+
+        assert_eq!(meta.biome_id(0, -64, 0), Some(1));
+        assert_eq!(meta.biome_id(0, -63, 0), Some(2));
+        assert_eq!(meta.biome_id(0, -47, 0), Some(2));
+        assert_eq!(meta.biome_id(5, -30, 9), Some(7));
+        assert_eq!(meta.biome_id(0, -16, 0), None);
+        assert_eq!(meta.biome_id(0, -65, 0), None);
+        assert_eq!(sections[0].get(0, 16, 0), None);
+        assert_eq!(sections[0].get(16, 1, 0), None);
+    }
+
+    #[test]
+    fn parse_data3d_errors() {
+        let mut bytes = heightmap();
+        bytes.push(COPY_PREVIOUS_HEADER);
+        let err = ChunkColumnMeta::parse_data3d(&mut Cursor::new(&bytes), 0..256).unwrap_err();
+        assert_eq!(err.to_string(), "First biome storage repeats a previous one");
+
+        let mut bytes = heightmap();
+        bytes.push(1 << 1);
+        bytes.extend_from_slice(&[0; 128 * 4]);
+        bytes.extend_from_slice(&(-1i32).to_le_bytes());
+        let err = ChunkColumnMeta::parse_data3d(&mut Cursor::new(&bytes), 0..256).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid palette length: -1");
+
+        // A palette claiming more entries than the record holds
+        let mut bytes = heightmap();
+        bytes.push(1 << 1);
+        bytes.extend_from_slice(&[0; 128 * 4]);
+        bytes.extend_from_slice(&i32::MAX.to_le_bytes());
+        let err = ChunkColumnMeta::parse_data3d(&mut Cursor::new(&bytes), 0..256).unwrap_err();
+        assert!(matches!(err, Error::UnexpectedEof));
+
+        let err = ChunkColumnMeta::parse_data3d(&mut Cursor::new(&heightmap()[..100]), 0..256).unwrap_err();
+        assert!(matches!(err, Error::UnexpectedEof));
+
+        // A run of copy markers longer than the dimension is tall
+        let mut bytes = heightmap();
+        bytes.push(0);
+        bytes.extend_from_slice(&7i32.to_le_bytes());
+        bytes.extend_from_slice(&[COPY_PREVIOUS_HEADER; 23]);
+        let meta = ChunkColumnMeta::parse_data3d(&mut Cursor::new(&bytes), -64..320).unwrap();
+        assert!(matches!(meta.biomes, Biomes::Sections { ref sections, .. } if sections.len() == 24));
+        bytes.extend_from_slice(&[COPY_PREVIOUS_HEADER; 10_000]);
+        let err = ChunkColumnMeta::parse_data3d(&mut Cursor::new(&bytes), -64..320).unwrap_err();
+        assert_eq!(err.to_string(), "More than 24 biome storages");
+    }
+
+    #[test]
+    fn parse_data2d() {
+        let mut bytes = heightmap();
+        bytes.extend((0..COLUMN_COUNT).map(|column| (column % 7) as u8));
+
+        let meta = ChunkColumnMeta::parse_data2d(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(meta.height(15, 15), Some(255));
+        assert_eq!(meta.biome_id(3, 100, 2), Some(35 % 7));
+        assert_eq!(meta.biome_id(3, -100, 2), Some(35 % 7));
+        assert_eq!(meta.biome_id(0, 0, 16), None);
+
+        assert!(ChunkColumnMeta::parse_data2d(&mut Cursor::new(&bytes[..600])).is_err());
+    }
+}
@@ -0,0 +1,7 @@
+pub mod block_entity;
+pub mod column;
+pub mod subchunk;
+
+pub use block_entity::{BlockEntity, BlockEntityKind};
+pub use column::{BiomeStorage, Biomes, ChunkColumnMeta};
+pub use subchunk::{BlockStorage, SubChunk};
@@ -0,0 +1,443 @@
+use std::io::{Read, Write};
+
+use crate::block::{legacy, BlockState};
+use crate::error::{Error, Result};
+use crate::nbt::Tag;
+
+/// Number of blocks along each side of a subchunk.
+pub const SUB_CHUNK_SIZE: usize = 16;
+
+/// Number of blocks in a subchunk.
+pub const SUB_CHUNK_VOLUME: usize = SUB_CHUNK_SIZE * SUB_CHUNK_SIZE * SUB_CHUNK_SIZE;
+
+const VALID_BITS_PER_BLOCK: [u8; 9] = [0, 1, 2, 3, 4, 5, 6, 8, 16];
+
+/// Bits per block the encoder picks from, smallest first.
+const ENCODED_BITS_PER_BLOCK: [u8; 8] = [1, 2, 3, 4, 5, 6, 8, 16];
+
+/// Reads the palette indices of the 4096 blocks of a subchunk, packed into
+/// little-endian 32-bit words without spanning word boundaries.
+pub(crate) fn read_indices<R: Read>(reader: &mut R, bits_per_block: u8) -> Result<Vec<u16>> {
+    if !VALID_BITS_PER_BLOCK.contains(&bits_per_block) {
+        return Err(Error::invalid_data(format!("Invalid bits per block: {}", bits_per_block)));
+    }
+
+    let mut indices = vec![0; SUB_CHUNK_VOLUME];
+    if bits_per_block > 0 {
+        let bits_per_block = bits_per_block as usize;
+        let blocks_per_word = 32 / bits_per_block;
+        let word_count = SUB_CHUNK_VOLUME.div_ceil(blocks_per_word);
+        let mask = (1u32 << bits_per_block) - 1;
+
+        let mut words_buf = vec![0; word_count * 4];
+        reader.read_exact(&mut words_buf)?;
+        for (block, index) in indices.iter_mut().enumerate() {
+            let word_offset = block / blocks_per_word * 4;
+            let word = u32::from_le_bytes([
+                words_buf[word_offset],
+                words_buf[word_offset + 1],
+                words_buf[word_offset + 2],
+                words_buf[word_offset + 3],
+            ]);
+            let shift = (block % blocks_per_word) * bits_per_block;
+            *index = ((word >> shift) & mask) as u16;
+        }
+    }
+    Ok(indices)
+}
+
+/// One storage layer of a subchunk: a palette of block states and, for each
+/// block, its index in the palette. Blocks are stored in XZY order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockStorage {
+    pub indices: Vec<u16>,
+    pub palette: Vec<Tag>,
+}
+
+impl BlockStorage {
+    /// Returns the position of a block in `indices`, with coordinates relative
+    /// to the subchunk.
+    pub fn index(x: usize, y: usize, z: usize) -> usize {
+        (x << 8) | (z << 4) | y
+    }
+
+    /// Returns the position of a block in `indices`, or `None` when a
+    /// coordinate is outside the subchunk.
+    pub fn checked_index(x: usize, y: usize, z: usize) -> Option<usize> {
+        if x >= SUB_CHUNK_SIZE || y >= SUB_CHUNK_SIZE || z >= SUB_CHUNK_SIZE {
+            return None;
+        }
+        Some(Self::index(x, y, z))
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<&Tag> {
+        let palette_index = *self.indices.get(Self::checked_index(x, y, z)?)?;
+        self.palette.get(palette_index as usize)
+    }
+
+    /// Sets the block state at a position, adding it to the palette when it
+    /// is not there yet.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, block_state: Tag) -> Result<()> {
+        let index = Self::checked_index(x, y, z)
+            .filter(|&index| index < self.indices.len())
+            .ok_or_else(|| Error::invalid_input(format!("Block {}, {}, {} is outside the subchunk", x, y, z)))?;
+        let palette_index = match self.palette.iter().position(|palette_entry| *palette_entry == block_state) {
+            Some(palette_index) => palette_index,
+            None => self.palette.len(),
+        };
+        let palette_index = u16::try_from(palette_index)
+            .map_err(|_| Error::invalid_input("Subchunk palette is full"))?;
+        self.indices[index] = palette_index;
+        if palette_index as usize == self.palette.len() {
+            self.palette.push(block_state);
+        }
+        Ok(())
+    }
+
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self> {
+        // Read the storage header: bits per block and the runtime flag
+        let mut header_buf = [0; 1];
+        reader.read_exact(&mut header_buf)?;
+        let bits_per_block = header_buf[0] >> 1;
+        if header_buf[0] & 1 != 0 {
+            return Err(Error::invalid_data("Runtime block storage is not supported"));
+        }
+
+        // Unpack the palette indices from the words
+        let indices = read_indices(reader, bits_per_block)?;
+
+        // Read the palette of block states
+        let mut palette_length_buf = [0; 4];
+        reader.read_exact(&mut palette_length_buf)?;
+        let palette_length = i32::from_le_bytes(palette_length_buf);
+        if palette_length < 0 {
+            return Err(Error::invalid_data(format!("Invalid palette length: {}", palette_length)));
+        }
+        // Never more entries than blocks are reserved, whatever the length says
+        let mut palette = Vec::with_capacity((palette_length as usize).min(SUB_CHUNK_VOLUME));
+        for _ in 0..palette_length {
+            palette.push(Tag::parse(reader)?);
+        }
+
+        Ok(BlockStorage {
+            indices,
+            palette,
+        })
+    }
+
+    /// Returns a copy of this storage without the palette entries that no
+    /// block uses, keeping the remaining entries in their original order.
+    pub fn compacted(&self) -> Result<Self> {
+        let mut used = vec![false; self.palette.len()];
+        for &index in &self.indices {
+            match used.get_mut(index as usize) {
+                Some(used) => *used = true,
+                None => return Err(Error::invalid_input(format!("Palette index out of range: {}", index))),
+            }
+        }
+
+        let mut remapped = vec![0; self.palette.len()];
+        let mut palette = Vec::new();
+        for (index, block_state) in self.palette.iter().enumerate() {
+            if used[index] {
+                remapped[index] = palette.len() as u16;
+                palette.push(block_state.clone());
+            }
+        }
+
+        Ok(BlockStorage {
+            indices: self.indices.iter().map(|&index| remapped[index as usize]).collect(),
+            palette,
+        })
+    }
+
+    /// Writes the storage with its palette compacted and the smallest bits
+    /// per block that fits it.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        if self.indices.len() != SUB_CHUNK_VOLUME {
+            return Err(Error::invalid_input(format!("Invalid block count: {}", self.indices.len())));
+        }
+        let storage = self.compacted()?;
+
+        let bits_per_block = ENCODED_BITS_PER_BLOCK.iter()
+            .copied()
+            .find(|&bits_per_block| storage.palette.len() <= 1 << bits_per_block)
+            .ok_or_else(|| Error::invalid_input(format!("Palette too large: {}", storage.palette.len())))?;
+        writer.write_all(&[bits_per_block << 1])?;
+
+        // Pack the palette indices into words
+        let bits_per_block = bits_per_block as usize;
+        let blocks_per_word = 32 / bits_per_block;
+        for chunk in storage.indices.chunks(blocks_per_word) {
+            let mut word = 0u32;
+            for (position, &index) in chunk.iter().enumerate() {
+                word |= (index as u32) << (position * bits_per_block);
+            }
+            writer.write_all(&word.to_le_bytes())?;
+        }
+
+        // Write the palette of block states
+        writer.write_all(&(storage.palette.len() as i32).to_le_bytes())?;
+        for block_state in &storage.palette {
+            block_state.write(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// A 16x16x16 section of a chunk, as stored under the `SubChunkPrefix` key.
+/// The first layer holds the blocks and the second one, when present, the
+/// blocks they are waterlogged with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubChunk {
+    pub version: u8,
+    pub y_index: Option<i8>,
+    pub layers: Vec<BlockStorage>,
+}
+
+impl SubChunk {
+    /// Creates a version 9 subchunk holding only air.
+    pub fn air(y_index: i8) -> Self {
+        SubChunk {
+            version: 9,
+            y_index: Some(y_index),
+            layers: vec![BlockStorage {
+                indices: vec![0; SUB_CHUNK_VOLUME],
+                palette: vec![BlockState::new("minecraft:air").to_tag()],
+            }],
+        }
+    }
+
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut version_buf = [0; 1];
+        reader.read_exact(&mut version_buf)?;
+        let version = version_buf[0];
+
+        let (layer_count, y_index) = match version {
+            1 => (1, None),
+            8 | 9 => {
+                let mut layer_count_buf = [0; 1];
+                reader.read_exact(&mut layer_count_buf)?;
+                let y_index = if version == 9 {
+                    let mut y_index_buf = [0; 1];
+                    reader.read_exact(&mut y_index_buf)?;
+                    Some(y_index_buf[0] as i8)
+                } else {
+                    None
+                };
+                (layer_count_buf[0], y_index)
+            }
+            0 | 2..=7 => return Self::parse_legacy(reader, version),
+            _ => return Err(Error::UnsupportedSubChunkVersion(version)),
+        };
+
+        let mut layers = Vec::with_capacity(layer_count as usize);
+        for _ in 0..layer_count {
+            layers.push(BlockStorage::parse(reader)?);
+        }
+
+        Ok(SubChunk {
+            version,
+            y_index,
+            layers,
+        })
+    }
+
+    /// Parses the legacy versions, which store a numeric id and a data value
+    /// per block. The blocks are upgraded into a block state palette.
+    fn parse_legacy<R: Read>(reader: &mut R, version: u8) -> Result<Self> {
+        let mut block_ids_buf = vec![0; SUB_CHUNK_VOLUME];
+        reader.read_exact(&mut block_ids_buf)?;
+        let mut block_data_buf = vec![0; SUB_CHUNK_VOLUME / 2];
+        reader.read_exact(&mut block_data_buf)?;
+
+        let mut legacy_palette: Vec<(u8, u8)> = Vec::new();
+        let mut indices = Vec::with_capacity(SUB_CHUNK_VOLUME);
+        for (block, &block_id) in block_ids_buf.iter().enumerate() {
+            let block_data = block_data_buf[block / 2] >> (block % 2 * 4) & 0xF;
+            let index = match legacy_palette.iter().position(|&entry| entry == (block_id, block_data)) {
+                Some(index) => index,
+                None => {
+                    legacy_palette.push((block_id, block_data));
+                    legacy_palette.len() - 1
+                }
+            };
+            indices.push(index as u16);
+        }
+
+        let palette = legacy_palette.into_iter()
+            .map(|(block_id, block_data)| legacy::upgrade(block_id, block_data).to_tag())
+            .collect();
+
+        Ok(SubChunk {
+            version,
+            y_index: None,
+            layers: vec![BlockStorage { indices, palette }],
+        })
+    }
+
+    /// Writes the subchunk in version 9 when it has a Y index and in version 8
+    /// otherwise, with every storage layer compacted.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let layer_count = u8::try_from(self.layers.len())
+            .map_err(|_| Error::invalid_input(format!("Too many layers: {}", self.layers.len())))?;
+        match self.y_index {
+            Some(y_index) => writer.write_all(&[9, layer_count, y_index as u8])?,
+            None => writer.write_all(&[8, layer_count])?,
+        }
+        for layer in &self.layers {
+            layer.write(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the block state at the given position of the first layer.
+    pub fn block(&self, x: usize, y: usize, z: usize) -> Option<&Tag> {
+        self.layers.first()?.get(x, y, z)
+    }
+
+    /// Sets the block state at the given position of the first layer.
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, block_state: Tag) -> Result<()> {
+        self.layers.first_mut()
+            .ok_or_else(|| Error::invalid_data("Subchunk without block storage"))?
+            .set(x, y, z, block_state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::Choice;
+    use std::io::Cursor;
+
+    fn block_state(name: &str) -> Tag {
+        Tag::new("", Choice::Vec(vec![
+            Tag::new("name", Choice::String(name.to_string())),
+            Tag::new("states", Choice::Vec(Vec::new())),
+            Tag::new("version", Choice::Int32(18_090_528)),
+        ]))
+    }
+
+    #[test]
+    fn parse_version_9() {
+        let mut bytes = vec![9, 1, 0xFC, 1 << 1];
+        // 32 blocks per word, block (0, 1, 0) is stone and the rest is air
+        let mut words = vec![0u32; 128];
+        words[0] = 0b10;
+        for word in words {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes.extend_from_slice(&2i32.to_le_bytes());
+        block_state("minecraft:air").write(&mut bytes).unwrap();
+        block_state("minecraft:stone").write(&mut bytes).unwrap();
+
+        let sub_chunk = SubChunk::parse(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(sub_chunk.y_index, Some(-4));
+        assert_eq!(sub_chunk.layers.len(), 1);
+        assert_eq!(sub_chunk.block(0, 1, 0), Some(&block_state("minecraft:stone")));
+        assert_eq!(sub_chunk.block(0, 0, 0), Some(&block_state("minecraft:air")));
+        assert_eq!(sub_chunk.block(15, 15, 15), Some(&block_state("minecraft:air")));
+    }
+
+    #[test]
+    fn parse_corrupt_palette_length() {
+        for palette_length in [-1, i32::MAX] {
+            let mut bytes = vec![8, 1, 1 << 1];
+            bytes.extend_from_slice(&[0; 128 * 4]);
+            bytes.extend_from_slice(&palette_length.to_le_bytes());
+            let err = SubChunk::parse(&mut Cursor::new(&bytes)).unwrap_err();
+            match palette_length {
+                -1 => assert_eq!(err.to_string(), "Invalid palette length: -1"),
+                _ => assert!(matches!(err.cause(), Error::UnexpectedEof), "{:?}", err),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_legacy() {
+        for version in [0, 2, 7] {
+            let mut bytes = vec![version];
+            // Block (0, 1, 0) is stone and block (0, 2, 0) red wool
+            let mut block_ids = vec![0u8; SUB_CHUNK_VOLUME];
+            block_ids[BlockStorage::index(0, 1, 0)] = 1;
+            block_ids[BlockStorage::index(0, 2, 0)] = 35;
+            let mut block_data = vec![0u8; SUB_CHUNK_VOLUME / 2];
+            block_data[BlockStorage::index(0, 2, 0) / 2] = 14;
+            bytes.extend_from_slice(&block_ids);
+            bytes.extend_from_slice(&block_data);
+
+            let sub_chunk = SubChunk::parse(&mut Cursor::new(&bytes)).unwrap();
+            assert_eq!((sub_chunk.version, sub_chunk.y_index), (version, None));
+            assert_eq!(sub_chunk.layers.len(), 1);
+            assert_eq!(sub_chunk.layers[0].palette.len(), 3);
+            assert_eq!(sub_chunk.block(0, 0, 0), Some(&legacy::upgrade(0, 0).to_tag()));
+            assert_eq!(sub_chunk.block(0, 1, 0), Some(&legacy::upgrade(1, 0).to_tag()));
+            assert_eq!(sub_chunk.block(0, 2, 0), Some(&legacy::upgrade(35, 14).to_tag()));
+            assert_ne!(sub_chunk.block(0, 2, 0), Some(&legacy::upgrade(35, 0).to_tag()));
+            assert_eq!(sub_chunk.block(15, 15, 15), Some(&legacy::upgrade(0, 0).to_tag()));
+        }
+
+        let err = SubChunk::parse(&mut Cursor::new(&[0; 100])).unwrap_err();
+        assert!(matches!(err, Error::UnexpectedEof));
+    }
+
+    #[test]
+    fn write_compacts_palette() {
+        let palette: Vec<Tag> = (0..40).map(|i| block_state(&format!("minecraft:block_{}", i))).collect();
+        let mut indices = vec![0; SUB_CHUNK_VOLUME];
+        for (block, index) in indices.iter_mut().enumerate() {
+            // Only the even entries are used, which fits in 5 bits once compacted
+            *index = (block % 20 * 2) as u16;
+        }
+        let water = BlockStorage {
+            indices: vec![1; SUB_CHUNK_VOLUME],
+            palette: vec![block_state("minecraft:air"), block_state("minecraft:water")],
+        };
+        let sub_chunk = SubChunk {
+            version: 9,
+            y_index: Some(3),
+            layers: vec![BlockStorage { indices, palette }, water],
+        };
+
+        let mut bytes = Vec::new();
+        sub_chunk.write(&mut bytes).unwrap();
+        assert_eq!(&bytes[..4], &[9, 2, 3, 5 << 1]);
+
+        let parsed = SubChunk::parse(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(parsed.layers[0].palette.len(), 20);
+        assert_eq!(parsed.layers[1].palette, vec![block_state("minecraft:water")]);
+        for x in 0..SUB_CHUNK_SIZE {
+            for y in 0..SUB_CHUNK_SIZE {
+                for z in 0..SUB_CHUNK_SIZE {
+                    assert_eq!(parsed.block(x, y, z), sub_chunk.block(x, y, z));
+                    assert_eq!(parsed.layers[1].get(x, y, z), sub_chunk.layers[1].get(x, y, z));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn set_block() {
+        let mut sub_chunk = SubChunk::air(0);
+        sub_chunk.set_block(15, 0, 3, block_state("minecraft:stone")).unwrap();
+        sub_chunk.set_block(0, 15, 0, block_state("minecraft:stone")).unwrap();
+        assert_eq!(sub_chunk.layers[0].palette.len(), 2);
+        assert_eq!(sub_chunk.block(15, 0, 3), Some(&block_state("minecraft:stone")));
+        assert_eq!(sub_chunk.block(0, 15, 0), Some(&block_state("minecraft:stone")));
+
+        // Coordinates past the subchunk do not wrap onto other blocks
+        for (x, y, z) in [(0, 16, 0), (16, 0, 0), (0, 0, 16), (usize::MAX, 0, 0)] {
+            assert_eq!(sub_chunk.block(x, y, z), None);
+            let err = sub_chunk.set_block(x, y, z, block_state("minecraft:dirt")).unwrap_err();
+            assert!(matches!(err, Error::InvalidInput(_)), "{:?}", err);
+        }
+        assert_eq!(sub_chunk.block(0, 0, 1), Some(&block_state("minecraft:air")));
+        assert_eq!(sub_chunk.layers[0].palette.len(), 2);
+        assert_eq!(sub_chunk.layers[0].indices.iter().filter(|&&index| index == 1).count(), 2);
+
+        let err = SubChunk { version: 9, y_index: Some(0), layers: Vec::new() }
+            .set_block(0, 0, 0, block_state("minecraft:dirt"))
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidData(_)));
+    }
+}
@@ -0,0 +1,427 @@
+use std::fmt;
+
+use crate::error::{Error, Result};
+use crate::world::Dimension;
+
+/// The record stored under a chunk key, identified by the tag byte that
+/// follows the chunk coordinates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChunkRecord {
+    Data3D,
+    Version,
+    Data2D,
+    Data2DLegacy,
+    SubChunkPrefix(i8),
+    LegacyTerrain,
+    BlockEntity,
+    Entity,
+    PendingTicks,
+    LegacyBlockExtraData,
+    BiomeState,
+    FinalizedState,
+    ConversionData,
+    BorderBlocks,
+    HardcodedSpawners,
+    RandomTicks,
+    Checksums,
+    GenerationSeed,
+    GeneratedPreCavesAndCliffsBlending,
+    BlendingBiomeHeight,
+    MetaDataHash,
+    BlendingData,
+    ActorDigestVersion,
+    LegacyVersion,
+}
+
+impl ChunkRecord {
+    const SUB_CHUNK_PREFIX_TAG: u8 = 0x2F;
+
+    /// Decodes a record tag byte. `SubChunkPrefix` is not returned here since
+    /// it needs the Y index that follows the tag byte.
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        let record = match tag {
+            0x2B => ChunkRecord::Data3D,
+            0x2C => ChunkRecord::Version,
+            0x2D => ChunkRecord::Data2D,
+            0x2E => ChunkRecord::Data2DLegacy,
+            0x30 => ChunkRecord::LegacyTerrain,
+            0x31 => ChunkRecord::BlockEntity,
+            0x32 => ChunkRecord::Entity,
+            0x33 => ChunkRecord::PendingTicks,
+            0x34 => ChunkRecord::LegacyBlockExtraData,
+            0x35 => ChunkRecord::BiomeState,
+            0x36 => ChunkRecord::FinalizedState,
+            0x37 => ChunkRecord::ConversionData,
+            0x38 => ChunkRecord::BorderBlocks,
+            0x39 => ChunkRecord::HardcodedSpawners,
+            0x3A => ChunkRecord::RandomTicks,
+            0x3B => ChunkRecord::Checksums,
+            0x3C => ChunkRecord::GenerationSeed,
+            0x3D => ChunkRecord::GeneratedPreCavesAndCliffsBlending,
+            0x3E => ChunkRecord::BlendingBiomeHeight,
+            0x3F => ChunkRecord::MetaDataHash,
+            0x40 => ChunkRecord::BlendingData,
+            0x41 => ChunkRecord::ActorDigestVersion,
+            0x76 => ChunkRecord::LegacyVersion,
+            _ => return None,
+        };
+        Some(record)
+    }
+
+    pub fn tag(&self) -> u8 {
+        match self {
+            ChunkRecord::Data3D => 0x2B,
+            ChunkRecord::Version => 0x2C,
+            ChunkRecord::Data2D => 0x2D,
+            ChunkRecord::Data2DLegacy => 0x2E,
+            ChunkRecord::SubChunkPrefix(_) => Self::SUB_CHUNK_PREFIX_TAG,
+            ChunkRecord::LegacyTerrain => 0x30,
+            ChunkRecord::BlockEntity => 0x31,
+            ChunkRecord::Entity => 0x32,
+            ChunkRecord::PendingTicks => 0x33,
+            ChunkRecord::LegacyBlockExtraData => 0x34,
+            ChunkRecord::BiomeState => 0x35,
+            ChunkRecord::FinalizedState => 0x36,
+            ChunkRecord::ConversionData => 0x37,
+            ChunkRecord::BorderBlocks => 0x38,
+            ChunkRecord::HardcodedSpawners => 0x39,
+            ChunkRecord::RandomTicks => 0x3A,
+            ChunkRecord::Checksums => 0x3B,
+            ChunkRecord::GenerationSeed => 0x3C,
+            ChunkRecord::GeneratedPreCavesAndCliffsBlending => 0x3D,
+            ChunkRecord::BlendingBiomeHeight => 0x3E,
+            ChunkRecord::MetaDataHash => 0x3F,
+            ChunkRecord::BlendingData => 0x40,
+            ChunkRecord::ActorDigestVersion => 0x41,
+            ChunkRecord::LegacyVersion => 0x76,
+        }
+    }
+}
+
+/// A key of the form `x z [dimension] tag [y]`, with every integer encoded
+/// as a little-endian `i32`. The dimension is omitted for the Overworld.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChunkKey {
+    pub x: i32,
+    pub z: i32,
+    pub dimension: Dimension,
+    pub record: ChunkRecord,
+}
+
+impl ChunkKey {
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let (x, z, dimension, rest) = match bytes.len() {
+            9 | 10 => (read_i32(&bytes[0..4]), read_i32(&bytes[4..8]), Dimension::Overworld, &bytes[8..]),
+            13 | 14 => (read_i32(&bytes[0..4]), read_i32(&bytes[4..8]), read_dimension(&bytes[8..12])?, &bytes[12..]),
+            _ => return None,
+        };
+        let record = match rest {
+            [ChunkRecord::SUB_CHUNK_PREFIX_TAG, y] => ChunkRecord::SubChunkPrefix(*y as i8),
+            [tag] => ChunkRecord::from_tag(*tag)?,
+            _ => return None,
+        };
+        Some(ChunkKey { x, z, dimension, record })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(14);
+        bytes.extend_from_slice(&self.x.to_le_bytes());
+        bytes.extend_from_slice(&self.z.to_le_bytes());
+        write_dimension(&mut bytes, self.dimension);
+        bytes.push(self.record.tag());
+        if let ChunkRecord::SubChunkPrefix(y) = self.record {
+            bytes.push(y as u8);
+        }
+        bytes
+    }
+}
+
+/// The broad kind of record a key refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum KeyCategory {
+    ChunkRecord,
+    Player,
+    Map,
+    Village,
+    Structure,
+    Actor,
+    ActorDigest,
+    Global,
+    Unknown,
+}
+
+/// A decoded key of the Bedrock world database.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DbKey {
+    Chunk(ChunkKey),
+    /// `~local_player`
+    LocalPlayer,
+    /// `player_<id>` or, when `server` is set, `player_server_<id>`.
+    Player { server: bool, id: String },
+    /// `VILLAGE_<suffix>`
+    Village(String),
+    /// `map_<id>`
+    Map(i64),
+    /// `portals`
+    Portals,
+    /// `structuretemplate_<name>`
+    Structure(String),
+    /// `actorprefix` followed by the 8-byte unique id of an actor.
+    Actor(i64),
+    /// `digp` followed by chunk coordinates, listing the actors of a chunk.
+    ActorDigest { x: i32, z: i32, dimension: Dimension },
+    /// Any other textual key, such as `scoreboard` or `BiomeData`.
+    Named(String),
+    Unknown(Vec<u8>),
+}
+
+impl DbKey {
+    const LOCAL_PLAYER: &'static str = "~local_player";
+    const PLAYER_SERVER_PREFIX: &'static str = "player_server_";
+    const PLAYER_PREFIX: &'static str = "player_";
+    const VILLAGE_PREFIX: &'static str = "VILLAGE_";
+    const MAP_PREFIX: &'static str = "map_";
+    const PORTALS: &'static str = "portals";
+    const STRUCTURE_PREFIX: &'static str = "structuretemplate_";
+    const ACTOR_PREFIX: &'static [u8] = b"actorprefix";
+    const DIGEST_PREFIX: &'static [u8] = b"digp";
+
+    pub fn parse(bytes: &[u8]) -> Self {
+        if let Some(id) = bytes.strip_prefix(Self::ACTOR_PREFIX) {
+            if let Ok(id) = <[u8; 8]>::try_from(id) {
+                return DbKey::Actor(i64::from_le_bytes(id));
+            }
+        }
+
+        if let Some(position) = bytes.strip_prefix(Self::DIGEST_PREFIX) {
+            let dimension = match position.len() {
+                8 => Some(Dimension::Overworld),
+                12 => read_dimension(&position[8..12]),
+                _ => None,
+            };
+            if let Some(dimension) = dimension {
+                return DbKey::ActorDigest {
+                    x: read_i32(&position[0..4]),
+                    z: read_i32(&position[4..8]),
+                    dimension,
+                };
+            }
+        }
+
+        if let Ok(name) = std::str::from_utf8(bytes) {
+            if name == Self::LOCAL_PLAYER {
+                return DbKey::LocalPlayer;
+            }
+            if name == Self::PORTALS {
+                return DbKey::Portals;
+            }
+            if let Some(id) = name.strip_prefix(Self::PLAYER_SERVER_PREFIX) {
+                return DbKey::Player { server: true, id: id.to_string() };
+            }
+            if let Some(id) = name.strip_prefix(Self::PLAYER_PREFIX) {
+                return DbKey::Player { server: false, id: id.to_string() };
+            }
+            if let Some(suffix) = name.strip_prefix(Self::VILLAGE_PREFIX) {
+                return DbKey::Village(suffix.to_string());
+            }
+            if let Some(name) = name.strip_prefix(Self::STRUCTURE_PREFIX) {
+                return DbKey::Structure(name.to_string());
+            }
+            if let Some(id_text) = name.strip_prefix(Self::MAP_PREFIX) {
+                // Only canonical numbers, so that the key encodes back to the same bytes
+                if let Ok(id) = id_text.parse::<i64>() {
+                    if id.to_string() == id_text {
+                        return DbKey::Map(id);
+                    }
+                }
+            }
+        }
+
+        if let Some(chunk_key) = ChunkKey::parse(bytes) {
+            return DbKey::Chunk(chunk_key);
+        }
+
+        match std::str::from_utf8(bytes) {
+            Ok(name) if !name.is_empty() && name.bytes().all(|byte| byte.is_ascii_graphic()) => {
+                DbKey::Named(name.to_string())
+            }
+            _ => DbKey::Unknown(bytes.to_vec()),
+        }
+    }
+
+    /// Parses a key like `parse`, but fails on keys that match none of the
+    /// known layouts instead of returning `DbKey::Unknown`.
+    pub fn try_parse(bytes: &[u8]) -> Result<Self> {
+        match Self::parse(bytes) {
+            DbKey::Unknown(bytes) => Err(Error::UnknownKeyFormat(bytes)),
+            key => Ok(key),
+        }
+    }
+
+    pub fn category(&self) -> KeyCategory {
+        match self {
+            DbKey::Chunk(_) => KeyCategory::ChunkRecord,
+            DbKey::LocalPlayer | DbKey::Player { .. } => KeyCategory::Player,
+            DbKey::Village(_) => KeyCategory::Village,
+            DbKey::Map(_) => KeyCategory::Map,
+            DbKey::Structure(_) => KeyCategory::Structure,
+            DbKey::Actor(_) => KeyCategory::Actor,
+            DbKey::ActorDigest { .. } => KeyCategory::ActorDigest,
+            DbKey::Portals | DbKey::Named(_) => KeyCategory::Global,
+            DbKey::Unknown(_) => KeyCategory::Unknown,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            DbKey::Chunk(chunk_key) => chunk_key.to_bytes(),
+            DbKey::LocalPlayer => Self::LOCAL_PLAYER.as_bytes().to_vec(),
+            DbKey::Player { server: true, id } => format!("{}{}", Self::PLAYER_SERVER_PREFIX, id).into_bytes(),
+            DbKey::Player { server: false, id } => format!("{}{}", Self::PLAYER_PREFIX, id).into_bytes(),
+            DbKey::Village(suffix) => format!("{}{}", Self::VILLAGE_PREFIX, suffix).into_bytes(),
+            DbKey::Map(id) => format!("{}{}", Self::MAP_PREFIX, id).into_bytes(),
+            DbKey::Portals => Self::PORTALS.as_bytes().to_vec(),
+            DbKey::Structure(name) => format!("{}{}", Self::STRUCTURE_PREFIX, name).into_bytes(),
+            DbKey::Actor(id) => [Self::ACTOR_PREFIX, &id.to_le_bytes()].concat(),
+            DbKey::ActorDigest { x, z, dimension } => {
+                let mut bytes = Self::DIGEST_PREFIX.to_vec();
+                bytes.extend_from_slice(&x.to_le_bytes());
+                bytes.extend_from_slice(&z.to_le_bytes());
+                write_dimension(&mut bytes, *dimension);
+                bytes
+            }
+            DbKey::Named(name) => name.as_bytes().to_vec(),
+            DbKey::Unknown(bytes) => bytes.clone(),
+        }
+    }
+}
+
+impl fmt::Display for DbKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DbKey::Chunk(chunk_key) => {
+                write!(f, "chunk({}, {}", chunk_key.x, chunk_key.z)?;
+                if chunk_key.dimension != Dimension::Overworld {
+                    write!(f, ", {:?}", chunk_key.dimension)?;
+                }
+                write!(f, ") {:?}", chunk_key.record)
+            }
+            DbKey::Actor(id) => write!(f, "actorprefix{:016x}", id),
+            DbKey::ActorDigest { x, z, dimension } => {
+                write!(f, "digp({}, {}", x, z)?;
+                if *dimension != Dimension::Overworld {
+                    write!(f, ", {:?}", dimension)?;
+                }
+                write!(f, ")")
+            }
+            DbKey::Unknown(bytes) => {
+                for byte in bytes {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+            _ => write!(f, "{}", String::from_utf8_lossy(&self.to_bytes())),
+        }
+    }
+}
+
+/// Writes the dimension id of a key, which is omitted for the Overworld.
+fn write_dimension(bytes: &mut Vec<u8>, dimension: Dimension) {
+    if dimension != Dimension::Overworld {
+        bytes.extend_from_slice(&dimension.id().to_le_bytes());
+    }
+}
+
+/// Reads the dimension id of a key. An explicit Overworld id is rejected,
+/// since `write_dimension` would not encode the key back to the same bytes.
+fn read_dimension(bytes: &[u8]) -> Option<Dimension> {
+    Dimension::from_id(read_i32(bytes)).filter(|&dimension| dimension != Dimension::Overworld)
+}
+
+fn read_i32(bytes: &[u8]) -> i32 {
+    i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_keys() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(-3i32).to_le_bytes());
+        bytes.extend_from_slice(&7i32.to_le_bytes());
+        bytes.extend_from_slice(&1i32.to_le_bytes());
+        bytes.extend_from_slice(&[0x2F, 0xFC]);
+
+        let key = DbKey::parse(&bytes);
+        assert_eq!(key, DbKey::Chunk(ChunkKey {
+            x: -3,
+            z: 7,
+            dimension: Dimension::Nether,
+            record: ChunkRecord::SubChunkPrefix(-4),
+        }));
+        assert_eq!(key.to_bytes(), bytes);
+
+        let overworld_version = [0, 0, 0, 0, 1, 0, 0, 0, 0x2C];
+        assert_eq!(DbKey::parse(&overworld_version), DbKey::Chunk(ChunkKey {
+            x: 0,
+            z: 1,
+            dimension: Dimension::Overworld,
+            record: ChunkRecord::Version,
+        }));
+
+        // An explicit Overworld id is not a chunk key, which keeps parsing
+        // and encoding inverse of each other
+        let mut explicit_overworld = overworld_version[..8].to_vec();
+        explicit_overworld.extend_from_slice(&0i32.to_le_bytes());
+        explicit_overworld.push(0x2C);
+        let key = DbKey::parse(&explicit_overworld);
+        assert_eq!(key, DbKey::Unknown(explicit_overworld.clone()));
+        assert_eq!(key.to_bytes(), explicit_overworld);
+    }
+
+    #[test]
+    fn digest_keys() {
+        for dimension_id in [None, Some(0i32), Some(1), Some(2), Some(3)] {
+            let mut bytes = b"digp".to_vec();
+            bytes.extend_from_slice(&(-1i32).to_le_bytes());
+            bytes.extend_from_slice(&2i32.to_le_bytes());
+            if let Some(dimension_id) = dimension_id {
+                bytes.extend_from_slice(&dimension_id.to_le_bytes());
+            }
+            let key = DbKey::parse(&bytes);
+            match dimension_id {
+                None | Some(1) | Some(2) => {
+                    let dimension = Dimension::from_id(dimension_id.unwrap_or(0)).unwrap();
+                    assert_eq!(key, DbKey::ActorDigest { x: -1, z: 2, dimension });
+                }
+                _ => assert_eq!(key.category(), KeyCategory::Unknown),
+            }
+            assert_eq!(key.to_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn string_keys() {
+        let keys: [(&[u8], DbKey); 8] = [
+            (b"~local_player", DbKey::LocalPlayer),
+            (b"player_server_1234", DbKey::Player { server: true, id: "1234".to_string() }),
+            (b"player_abcd", DbKey::Player { server: false, id: "abcd".to_string() }),
+            (b"VILLAGE_1234_INFO", DbKey::Village("1234_INFO".to_string())),
+            (b"map_-42", DbKey::Map(-42)),
+            (b"map_007", DbKey::Named("map_007".to_string())),
+            (b"portals", DbKey::Portals),
+            (b"scoreboard", DbKey::Named("scoreboard".to_string())),
+        ];
+        for (bytes, key) in keys {
+            assert_eq!(DbKey::parse(bytes), key);
+            assert_eq!(key.to_bytes(), bytes);
+        }
+
+        let actor = [b"actorprefix".as_slice(), &5i64.to_le_bytes()].concat();
+        assert_eq!(DbKey::parse(&actor), DbKey::Actor(5));
+        assert_eq!(DbKey::parse(&[0xff, 0x00]), DbKey::Unknown(vec![0xff, 0x00]));
+        assert!(matches!(DbKey::try_parse(&[0xff, 0x00]), Err(Error::UnknownKeyFormat(bytes)) if bytes == [0xff, 0x00]));
+    }
+}
@@ -0,0 +1,180 @@
+pub mod key;
+
+pub use key::{ChunkKey, ChunkRecord, DbKey, KeyCategory};
+
+#[cfg(feature = "leveldb")]
+use std::path::Path;
+
+#[cfg(feature = "leveldb")]
+use crate::chunk::BlockEntity;
+#[cfg(feature = "leveldb")]
+use crate::error::{Error, Result};
+#[cfg(feature = "leveldb")]
+use crate::entity::{self, Entity};
+#[cfg(feature = "leveldb")]
+use crate::nbt::Tag;
+#[cfg(feature = "leveldb")]
+use crate::player::Player;
+#[cfg(feature = "leveldb")]
+use crate::world::Dimension;
+
+#[cfg(feature = "leveldb")]
+use leveldb::database::Database;
+#[cfg(feature = "leveldb")]
+use leveldb::iterator::Iterable;
+#[cfg(feature = "leveldb")]
+use leveldb::kv::KV;
+#[cfg(feature = "leveldb")]
+use leveldb::options::{Options, ReadOptions, WriteOptions};
+
+#[cfg(feature = "leveldb")]
+impl db_key::Key for DbKey {
+    fn from_u8(key: &[u8]) -> Self {
+        DbKey::parse(key)
+    }
+
+    fn as_slice<T, F: Fn(&[u8]) -> T>(&self, f: F) -> T {
+        f(&self.to_bytes())
+    }
+}
+
+#[cfg(feature = "leveldb")]
+pub fn open(world_dir: &str) -> Result<Database<DbKey>> {
+    let mut options = Options::new();
+    options.block_size = Some(4096);
+    let level_db_path = Path::new(world_dir).join("db");
+    Database::open(&level_db_path, options).map_err(Error::LevelDb)
+}
+
+/// Iterates over every entry of the database in key order, with the keys
+/// decoded. Use `DbKey::category` to classify them.
+#[cfg(feature = "leveldb")]
+pub fn entries(database: &Database<DbKey>) -> impl Iterator<Item = (DbKey, Vec<u8>)> + '_ {
+    database.iter(ReadOptions::new())
+}
+
+/// Iterates over every key of the database in key order, without reading
+/// the values.
+#[cfg(feature = "leveldb")]
+pub fn keys(database: &Database<DbKey>) -> impl Iterator<Item = DbKey> + '_ {
+    database.keys_iter(ReadOptions::new())
+}
+
+/// Reads the value of a key, reporting LevelDB failures as `Error::LevelDb`.
+#[cfg(feature = "leveldb")]
+pub fn get(database: &Database<DbKey>, key: &DbKey) -> Result<Option<Vec<u8>>> {
+    database.get(ReadOptions::new(), key).map_err(Error::LevelDb)
+}
+
+/// Writes the value of a key, reporting LevelDB failures as `Error::LevelDb`.
+#[cfg(feature = "leveldb")]
+pub fn put(database: &Database<DbKey>, key: &DbKey, value: &[u8]) -> Result<()> {
+    database.put(WriteOptions::new(), key, value).map_err(Error::LevelDb)
+}
+
+#[cfg(feature = "leveldb")]
+pub fn delete(database: &Database<DbKey>, key: &DbKey) -> Result<()> {
+    database.delete(WriteOptions::new(), key).map_err(Error::LevelDb)
+}
+
+/// Reads the block entities of a chunk, with the errors of the entries that
+/// were skipped as invalid.
+#[cfg(feature = "leveldb")]
+pub fn block_entities(database: &Database<DbKey>, x: i32, z: i32, dimension: Dimension) -> Result<(Vec<BlockEntity>, Vec<Error>)> {
+    let key = DbKey::Chunk(ChunkKey { x, z, dimension, record: ChunkRecord::BlockEntity });
+    match get(database, &key)? {
+        Some(value) => BlockEntity::parse_all(&value),
+        None => Ok((Vec::new(), Vec::new())),
+    }
+}
+
+/// Replaces the block entities of a chunk, removing the record when there
+/// are none left. Entries of the current record that are not valid block
+/// entities, which `block_entities` skips, are kept.
+#[cfg(feature = "leveldb")]
+pub fn put_block_entities(database: &Database<DbKey>, x: i32, z: i32, dimension: Dimension, block_entities: &[BlockEntity]) -> Result<()> {
+    let key = DbKey::Chunk(ChunkKey { x, z, dimension, record: ChunkRecord::BlockEntity });
+    let mut value = Vec::new();
+    if let Some(current) = get(database, &key)? {
+        for tag in Tag::parse_all(&current)? {
+            if BlockEntity::from_tag(tag.clone()).is_err() {
+                tag.write(&mut value)?;
+            }
+        }
+    }
+    value.extend(BlockEntity::write_all(block_entities)?);
+    if value.is_empty() {
+        delete(database, &key)
+    } else {
+        put(database, &key, &value)
+    }
+}
+
+/// Reads the entities of a chunk, both from the actors listed in its `digp`
+/// record and from its legacy `Entity` record.
+#[cfg(feature = "leveldb")]
+pub fn chunk_entities(database: &Database<DbKey>, x: i32, z: i32, dimension: Dimension) -> Result<Vec<Entity>> {
+    let mut entities = Vec::new();
+
+    if let Some(digest) = get(database, &DbKey::ActorDigest { x, z, dimension })? {
+        for id in entity::parse_digest(&digest)? {
+            if let Some(value) = get(database, &DbKey::Actor(id))? {
+                entities.extend(Entity::parse_all(&value)?);
+            }
+        }
+    }
+
+    let key = DbKey::Chunk(ChunkKey { x, z, dimension, record: ChunkRecord::Entity });
+    if let Some(value) = get(database, &key)? {
+        entities.extend(Entity::parse_all(&value)?);
+    }
+
+    Ok(entities)
+}
+
+/// Iterates over every entity of the world, whether stored as an actor or in
+/// a legacy chunk record.
+#[cfg(feature = "leveldb")]
+pub fn entities(database: &Database<DbKey>) -> impl Iterator<Item = Result<Entity>> + '_ {
+    entries(database).flat_map(|(key, value)| {
+        let entities = match key {
+            DbKey::Actor(_) | DbKey::Chunk(ChunkKey { record: ChunkRecord::Entity, .. }) => Entity::parse_all(&value),
+            _ => Ok(Vec::new()),
+        };
+        match entities {
+            Ok(entities) => entities.into_iter().map(Ok).collect(),
+            Err(err) => vec![Err(err)],
+        }
+    })
+}
+
+/// Reads the local player, if the world has one.
+#[cfg(feature = "leveldb")]
+pub fn local_player(database: &Database<DbKey>) -> Result<Option<Player>> {
+    get(database, &DbKey::LocalPlayer)?
+        .map(|value| Player::parse(&value))
+        .transpose()
+}
+
+/// Reads every player record holding player data, skipping the `player_<id>`
+/// records that only link to a `player_server_<id>` record.
+#[cfg(feature = "leveldb")]
+pub fn players(database: &Database<DbKey>) -> Result<Vec<(DbKey, Player)>> {
+    let mut players = Vec::new();
+    for (key, value) in entries(database) {
+        if key.category() != KeyCategory::Player {
+            continue;
+        }
+        let player = Player::parse(&value)?;
+        if player.server_id().is_none() {
+            players.push((key, player));
+        }
+    }
+    Ok(players)
+}
+
+/// Writes a player back under its key, such as `DbKey::LocalPlayer`.
+#[cfg(feature = "leveldb")]
+pub fn put_player(database: &Database<DbKey>, key: &DbKey, player: &Player) -> Result<()> {
+    put(database, key, &player.to_bytes()?)
+}
@@ -0,0 +1,158 @@
+use crate::error::{Error, Result};
+use crate::nbt::{Choice, Tag};
+
+/// An entity, read either from its own `actorprefix` record in modern worlds
+/// or from the `Entity` record of a chunk in older ones.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entity {
+    pub identifier: String,
+    pub unique_id: Option<i64>,
+    pub position: Option<[f32; 3]>,
+    /// Yaw and pitch, in degrees.
+    pub rotation: Option<[f32; 2]>,
+    pub health: Option<f32>,
+    pub custom_name: Option<String>,
+    pub tag: Tag,
+}
+
+impl Entity {
+    pub fn from_tag(tag: Tag) -> Result<Self> {
+        let value = |key: &str| tag.get(key).and_then(|child_tag| child_tag.choice_value.as_ref());
+
+        // Entities saved before identifiers existed only have a numeric id
+        let identifier = match (value("identifier"), value("id")) {
+            (Some(Choice::String(identifier)), _) => identifier.clone(),
+            (_, Some(Choice::Int32(id))) => id.to_string(),
+            _ => return Err(Error::invalid_data("Entity without an identifier")),
+        };
+
+        let health = value("Attributes")
+            .and_then(Choice::as_list)
+            .and_then(|attributes| {
+                attributes.iter()
+                    .filter_map(Choice::as_compound)
+                    .find(|attribute| attribute_name(attribute) == Some("minecraft:health"))
+            })
+            .and_then(|attribute| {
+                attribute.iter().find(|child_tag| child_tag.key == "Current")?.choice_value.as_ref()?.as_f32()
+            });
+
+        Ok(Entity {
+            identifier,
+            unique_id: value("UniqueID").and_then(Choice::as_i64),
+            position: value("Pos").and_then(Choice::as_floats),
+            rotation: value("Rotation").and_then(Choice::as_floats),
+            health,
+            custom_name: value("CustomName").and_then(Choice::as_str).map(str::to_string),
+            tag,
+        })
+    }
+
+    /// Parses the entities of an `Entity` chunk record or an `actorprefix`
+    /// record.
+    pub fn parse_all(bytes: &[u8]) -> Result<Vec<Self>> {
+        Tag::parse_all(bytes)?
+            .into_iter()
+            .map(Self::from_tag)
+            .collect()
+    }
+}
+
+/// Parses the value of a `digp` record: the unique ids of the actors of a
+/// chunk, which are also the suffixes of their `actorprefix` keys.
+pub fn parse_digest(bytes: &[u8]) -> Result<Vec<i64>> {
+    if !bytes.len().is_multiple_of(8) {
+        return Err(Error::invalid_data(format!("Invalid actor digest length: {}", bytes.len())));
+    }
+    Ok(bytes.chunks_exact(8)
+        .map(|id| i64::from_le_bytes([id[0], id[1], id[2], id[3], id[4], id[5], id[6], id[7]]))
+        .collect())
+}
+
+fn attribute_name(attribute: &[Tag]) -> Option<&str> {
+    attribute.iter().find(|child_tag| child_tag.key == "Name")?.choice_value.as_ref()?.as_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tests::push_name;
+
+    fn push_floats(buf: &mut Vec<u8>, name: &str, values: &[f32]) {
+        push_name(buf, 9, name);
+        buf.push(5);
+        buf.extend_from_slice(&(values.len() as i32).to_le_bytes());
+        for value in values {
+            buf.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn parse_all() {
+        let mut record = Vec::new();
+
+        push_name(&mut record, 10, "");
+        push_name(&mut record, 8, "identifier");
+        record.extend_from_slice(&16u16.to_le_bytes());
+        record.extend_from_slice(b"minecraft:zombie");
+        push_name(&mut record, 4, "UniqueID");
+        record.extend_from_slice(&(-42i64).to_le_bytes());
+        push_floats(&mut record, "Pos", &[1.5, 70.0, -3.25]);
+        push_floats(&mut record, "Rotation", &[90.0, -10.0]);
+        push_name(&mut record, 9, "Attributes");
+        record.push(10);
+        record.extend_from_slice(&2i32.to_le_bytes());
+        push_name(&mut record, 8, "Name");
+        record.extend_from_slice(&18u16.to_le_bytes());
+        record.extend_from_slice(b"minecraft:movement");
+        record.push(0);
+        push_name(&mut record, 5, "Current");
+        record.extend_from_slice(&12.0f32.to_le_bytes());
+        push_name(&mut record, 8, "Name");
+        record.extend_from_slice(&16u16.to_le_bytes());
+        record.extend_from_slice(b"minecraft:health");
+        record.push(0);
+        push_name(&mut record, 8, "CustomName");
+        record.extend_from_slice(&3u16.to_le_bytes());
+        record.extend_from_slice(b"Bob");
+        record.push(0);
+
+        push_name(&mut record, 10, "");
+        push_name(&mut record, 3, "id");
+        record.extend_from_slice(&2848i32.to_le_bytes());
+        push_floats(&mut record, "Pos", &[0.0, 64.0]);
+        record.push(0);
+
+        let entities = Entity::parse_all(&record).unwrap();
+        assert_eq!(entities.len(), 2);
+
+        let zombie = &entities[0];
+        assert_eq!(zombie.identifier, "minecraft:zombie");
+        assert_eq!(zombie.unique_id, Some(-42));
+        assert_eq!(zombie.position, Some([1.5, 70.0, -3.25]));
+        assert_eq!(zombie.rotation, Some([90.0, -10.0]));
+        assert_eq!(zombie.health, Some(12.0));
+        assert_eq!(zombie.custom_name.as_deref(), Some("Bob"));
+
+        let legacy = &entities[1];
+        assert_eq!(legacy.identifier, "2848");
+        assert_eq!((legacy.unique_id, legacy.position, legacy.health), (None, None, None));
+
+        let mut unnamed = Vec::new();
+        push_name(&mut unnamed, 10, "");
+        push_name(&mut unnamed, 1, "Invulnerable");
+        unnamed.push(1);
+        unnamed.push(0);
+        assert!(matches!(Entity::parse_all(&unnamed), Err(Error::InvalidData(_))));
+    }
+
+    #[test]
+    fn digest() {
+        let mut digest = Vec::new();
+        digest.extend_from_slice(&1i64.to_le_bytes());
+        digest.extend_from_slice(&(-8_589_934_591i64).to_le_bytes());
+        assert_eq!(parse_digest(&digest).unwrap(), vec![1, -8_589_934_591]);
+        assert_eq!(parse_digest(&[]).unwrap(), Vec::<i64>::new());
+        assert!(matches!(parse_digest(&digest[..12]), Err(Error::InvalidData(_))));
+    }
+}
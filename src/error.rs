@@ -0,0 +1,163 @@
+use std::fmt;
+use std::io;
+use std::string::FromUtf8Error;
+
+use crate::nbt::SnbtError;
+
+/// Errors of reading and writing world data.
+#[derive(Debug)]
+pub enum Error {
+    /// The data ended in the middle of a value.
+    UnexpectedEof,
+    /// An NBT tag type id outside 0 to 12, with the byte offset it was read
+    /// at and the path of keys leading to it, such as `abilities.flySpeed`.
+    InvalidTagType { id: u8, offset: u64, path: String },
+    /// An NBT string or key that is not valid UTF-8.
+    InvalidUtf8(FromUtf8Error),
+    /// An error inside NBT data, with the byte offset it was found at and the
+    /// path of keys leading to it, such as `Inventory[3].tag.ench[0]`.
+    Nbt { offset: u64, path: String, source: Box<Error> },
+    #[cfg(feature = "leveldb")]
+    LevelDb(leveldb::error::Error),
+    /// A database key that matches none of the known layouts.
+    UnknownKeyFormat(Vec<u8>),
+    UnsupportedSubChunkVersion(u8),
+    Snbt(SnbtError),
+    /// Data that is well-formed but does not make sense, such as a player
+    /// record that is not a compound.
+    InvalidData(String),
+    /// An argument that cannot be applied, such as a coordinate outside the
+    /// dimension.
+    InvalidInput(String),
+    Io(io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub(crate) fn invalid_data<M: Into<String>>(message: M) -> Self {
+        Error::InvalidData(message.into())
+    }
+
+    pub(crate) fn invalid_input<M: Into<String>>(message: M) -> Self {
+        Error::InvalidInput(message.into())
+    }
+
+    /// Returns the byte offset of an NBT error.
+    pub fn offset(&self) -> Option<u64> {
+        match self {
+            Error::InvalidTagType { offset, .. } | Error::Nbt { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+
+    /// Returns the key path of an NBT error, empty for the root.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Error::InvalidTagType { path, .. } | Error::Nbt { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Returns the underlying error, looking through the position context of
+    /// NBT errors.
+    pub fn cause(&self) -> &Error {
+        match self {
+            Error::Nbt { source, .. } => source.cause(),
+            _ => self,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnexpectedEof => write!(f, "Unexpected end of data"),
+            Error::InvalidTagType { id, offset, path } => {
+                write!(f, "Invalid tag type {} at offset {}", id, offset)?;
+                if !path.is_empty() {
+                    write!(f, " in {}", path)?;
+                }
+                Ok(())
+            }
+            Error::InvalidUtf8(err) => write!(f, "Invalid UTF-8: {}", err),
+            Error::Nbt { offset, path, source } => {
+                write!(f, "{} at offset {}", source, offset)?;
+                if !path.is_empty() {
+                    write!(f, " in {}", path)?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "leveldb")]
+            Error::LevelDb(err) => write!(f, "{}", err),
+            Error::UnknownKeyFormat(bytes) => {
+                write!(f, "Unknown key format: ")?;
+                for byte in bytes {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+            Error::UnsupportedSubChunkVersion(version) => write!(f, "Unsupported subchunk version: {}", version),
+            Error::Snbt(err) => write!(f, "Invalid SNBT: {}", err),
+            Error::InvalidData(message) | Error::InvalidInput(message) => write!(f, "{}", message),
+            Error::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::InvalidUtf8(err) => Some(err),
+            Error::Nbt { source, .. } => Some(source.as_ref()),
+            #[cfg(feature = "leveldb")]
+            Error::LevelDb(err) => Some(err),
+            Error::Snbt(err) => Some(err),
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => Error::UnexpectedEof,
+            _ => Error::Io(err),
+        }
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(err: FromUtf8Error) -> Self {
+        Error::InvalidUtf8(err)
+    }
+}
+
+impl From<SnbtError> for Error {
+    fn from(err: SnbtError) -> Self {
+        Error::Snbt(err)
+    }
+}
+
+#[cfg(feature = "leveldb")]
+impl From<leveldb::error::Error> for Error {
+    fn from(err: leveldb::error::Error) -> Self {
+        Error::LevelDb(err)
+    }
+}
+
+/// For callers that work with `io::Result`.
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err.cause() {
+            Error::Io(_) => match err {
+                Error::Io(err) => err,
+                err => io::Error::other(err),
+            },
+            Error::UnexpectedEof => io::Error::new(io::ErrorKind::UnexpectedEof, err),
+            Error::InvalidInput(_) => io::Error::new(io::ErrorKind::InvalidInput, err),
+            _ => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use crate::chunk::BlockEntity;
+use crate::error::{Error, Result};
+use crate::nbt::{Choice, Tag, TagType};
+use crate::player::Player;
+
+/// Stack size of most items. Tools, armor and other unstackable items stack
+/// to 1, and items such as ender pearls or snowballs to 16.
+pub const MAX_STACK_SIZE: u8 = 64;
+
+/// An item in an inventory slot. The compound it was read from is kept in
+/// `compound`, so that writing the item back only changes the fields that
+/// changed and keeps the others, including unknown ones, as they were.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ItemStack {
+    pub name: String,
+    pub count: u8,
+    pub damage: i16,
+    pub slot: Option<u8>,
+    pub tag: Option<Tag>,
+    pub compound: Vec<Tag>,
+}
+
+impl ItemStack {
+    pub fn new(name: &str, count: u8) -> Self {
+        ItemStack {
+            name: name.to_string(),
+            count,
+            damage: 0,
+            slot: None,
+            tag: None,
+            compound: Vec::new(),
+        }
+    }
+
+    /// Returns whether this is the placeholder the game writes in empty slots.
+    pub fn is_empty(&self) -> bool {
+        self.name.is_empty() || self.count == 0
+    }
+
+    /// Returns whether `other` can be merged into this stack.
+    pub fn stacks_with(&self, other: &ItemStack) -> bool {
+        self.name == other.name && self.damage == other.damage && self.tag == other.tag
+    }
+
+    pub fn from_compound(compound_tags: &[Tag]) -> Self {
+        let mut item = ItemStack::new("", 0);
+        for child_tag in compound_tags {
+            match (child_tag.key.as_str(), &child_tag.choice_value) {
+                ("Name", Some(Choice::String(name))) => item.name = name.clone(),
+                ("Count", Some(Choice::Byte(count))) => item.count = *count as u8,
+                ("Damage", Some(Choice::Int16(damage))) => item.damage = *damage,
+                ("Slot", Some(Choice::Byte(slot))) => item.slot = Some(*slot as u8),
+                ("tag", Some(Choice::Vec(_))) => item.tag = Some(child_tag.clone()),
+                _ => {}
+            }
+        }
+        item.compound = compound_tags.to_vec();
+        item
+    }
+
+    /// Returns the item as a compound. Items read from a compound get it back
+    /// with only the changed fields updated; new items get every field.
+    pub fn to_compound(&self) -> Vec<Tag> {
+        let read = Self::from_compound(&self.compound);
+        let is_new = self.compound.is_empty();
+        let mut compound_tags = self.compound.clone();
+
+        if is_new || self.count != read.count {
+            set_field(&mut compound_tags, Tag::new("Count", Choice::Byte(self.count as i8)));
+        }
+        if is_new || self.damage != read.damage {
+            set_field(&mut compound_tags, Tag::new("Damage", Choice::Int16(self.damage)));
+        }
+        if is_new || self.name != read.name {
+            set_field(&mut compound_tags, Tag::new("Name", Choice::String(self.name.clone())));
+        }
+        if self.slot != read.slot {
+            match self.slot {
+                Some(slot) => set_field(&mut compound_tags, Tag::new("Slot", Choice::Byte(slot as i8))),
+                None => compound_tags.retain(|child_tag| child_tag.key != "Slot"),
+            }
+        }
+        if self.tag != read.tag {
+            match &self.tag {
+                Some(tag) => set_field(&mut compound_tags, tag.clone()),
+                None => compound_tags.retain(|child_tag| child_tag.key != "tag"),
+            }
+        }
+        compound_tags
+    }
+}
+
+/// Replaces the field with the same key, or appends it.
+fn set_field(compound_tags: &mut Vec<Tag>, tag: Tag) {
+    match compound_tags.iter_mut().find(|child_tag| child_tag.key == tag.key) {
+        Some(child_tag) => *child_tag = tag,
+        None => compound_tags.push(tag),
+    }
+}
+
+/// The containers of a player.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlayerContainer {
+    Inventory,
+    Armor,
+    EnderChest,
+}
+
+impl PlayerContainer {
+    fn key(&self) -> &'static str {
+        match self {
+            PlayerContainer::Inventory => "Inventory",
+            PlayerContainer::Armor => "Armor",
+            PlayerContainer::EnderChest => "EnderChestInventory",
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            PlayerContainer::Inventory => 36,
+            PlayerContainer::Armor => 4,
+            PlayerContainer::EnderChest => 27,
+        }
+    }
+}
+
+/// The slots of a container. Player containers list every slot, with empty
+/// ones as placeholder items, while block entities only list the filled
+/// slots; the inventory is written back in the layout it was read from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Inventory {
+    pub slots: Vec<Option<ItemStack>>,
+    dense: bool,
+    slotted: bool,
+}
+
+impl Inventory {
+    fn from_list(list: &[Choice], size: usize, dense: bool) -> Result<Self> {
+        let mut slots = vec![None; size];
+        let mut slotted = !dense;
+        for (position, choice_value) in list.iter().enumerate() {
+            let compound_tags = choice_value.as_compound()
+                .ok_or_else(|| Error::invalid_data("Item is not a compound"))?;
+            let item = ItemStack::from_compound(compound_tags);
+            slotted |= item.slot.is_some();
+            let slot = item.slot.map_or(position, |slot| slot as usize);
+            if slot >= slots.len() {
+                slots.resize(slot + 1, None);
+            }
+            if !item.is_empty() {
+                slots[slot] = Some(item);
+            }
+        }
+        Ok(Inventory { slots, dense, slotted })
+    }
+
+    pub fn from_player(player: &Player, container: PlayerContainer) -> Result<Self> {
+        let list = player.tag.get(container.key())
+            .and_then(|tag| tag.choice_value.as_ref()?.as_list())
+            .unwrap_or(&[]);
+        Self::from_list(list, container.size(), true)
+    }
+
+    pub fn from_block_entity(block_entity: &BlockEntity) -> Result<Self> {
+        let size = match block_entity.id.as_str() {
+            "Furnace" | "BlastFurnace" | "Smoker" => 3,
+            "Hopper" | "BrewingStand" => 5,
+            "Dispenser" | "Dropper" => 9,
+            _ => 27,
+        };
+        Self::from_list(block_entity.items().unwrap_or(&[]), size, false)
+    }
+
+    pub fn get(&self, slot: usize) -> Option<&ItemStack> {
+        self.slots.get(slot)?.as_ref()
+    }
+
+    /// Adds items, first onto the stacks they merge with and then into empty
+    /// slots, with at most `max_stack_size` items per slot, such as
+    /// `MAX_STACK_SIZE` or 1 for tools. Returns the number of items that did
+    /// not fit.
+    pub fn add(&mut self, item: ItemStack, max_stack_size: u8) -> u8 {
+        let mut remaining = item.count;
+
+        for stack in self.slots.iter_mut().flatten() {
+            if remaining == 0 {
+                break;
+            }
+            if stack.stacks_with(&item) && stack.count < max_stack_size {
+                let moved = remaining.min(max_stack_size - stack.count);
+                stack.count += moved;
+                remaining -= moved;
+            }
+        }
+
+        for slot in self.slots.iter_mut().filter(|slot| slot.is_none()) {
+            if remaining == 0 {
+                break;
+            }
+            if max_stack_size == 0 {
+                break;
+            }
+            let moved = remaining.min(max_stack_size);
+            *slot = Some(ItemStack { count: moved, ..item.clone() });
+            remaining -= moved;
+        }
+
+        remaining
+    }
+
+    /// Removes up to `count` items with the given name, emptying the slots
+    /// that run out. Returns the number of items removed.
+    pub fn remove(&mut self, name: &str, count: u8) -> u8 {
+        let mut removed = 0;
+        for slot in self.slots.iter_mut() {
+            if removed == count {
+                break;
+            }
+            if let Some(stack) = slot {
+                if stack.name == name {
+                    let taken = (count - removed).min(stack.count);
+                    stack.count -= taken;
+                    removed += taken;
+                    if stack.count == 0 {
+                        *slot = None;
+                    }
+                }
+            }
+        }
+        removed
+    }
+
+    /// Puts an item in a slot, or empties it, and returns what was there.
+    pub fn replace(&mut self, slot: usize, item: Option<ItemStack>) -> Result<Option<ItemStack>> {
+        let current = self.slots.get_mut(slot)
+            .ok_or_else(|| Error::invalid_input(format!("Invalid slot: {}", slot)))?;
+        Ok(std::mem::replace(current, item))
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.iter_mut().for_each(|slot| *slot = None);
+    }
+
+    pub fn to_choice(&self) -> Choice {
+        let mut items = Vec::new();
+        for (position, slot) in self.slots.iter().enumerate() {
+            let mut item = match slot {
+                Some(item) => item.clone(),
+                None if self.dense => ItemStack::new("", 0),
+                None => continue,
+            };
+            item.slot = if self.slotted { Some(position as u8) } else { None };
+            items.push(Choice::Vec(item.to_compound()));
+        }
+        Choice::List(TagType::Compound, items)
+    }
+
+    pub fn write_to_player(&self, player: &mut Player, container: PlayerContainer) -> Result<()> {
+        player.tag.insert(Tag::new(container.key(), self.to_choice()))?;
+        Ok(())
+    }
+
+    pub fn write_to_block_entity(&self, block_entity: &mut BlockEntity) -> Result<()> {
+        block_entity.tag.insert(Tag::new("Items", self.to_choice()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_and_remove() {
+        let list = vec![
+            Choice::Vec(ItemStack { slot: Some(0), ..ItemStack::new("minecraft:dirt", 60) }.to_compound()),
+            Choice::Vec(ItemStack { slot: Some(1), ..ItemStack::new("", 0) }.to_compound()),
+            Choice::Vec(ItemStack { slot: Some(2), ..ItemStack::new("minecraft:stone", 1) }.to_compound()),
+        ];
+        let mut inventory = Inventory::from_list(&list, 3, true).unwrap();
+        assert_eq!(inventory.get(1), None);
+
+        assert_eq!(inventory.add(ItemStack::new("minecraft:dirt", 70), MAX_STACK_SIZE), 2);
+        assert_eq!(inventory.get(0).map(|item| item.count), Some(64));
+        assert_eq!(inventory.get(1).map(|item| item.count), Some(64));
+
+        assert_eq!(inventory.remove("minecraft:dirt", 100), 100);
+        assert_eq!(inventory.get(0), None);
+        assert_eq!(inventory.get(1).map(|item| item.count), Some(28));
+
+        let Choice::List(TagType::Compound, items) = inventory.to_choice() else {
+            panic!("inventory is not a list of compounds");
+        };
+        assert_eq!(items.len(), 3);
+        assert_eq!(ItemStack::from_compound(items[0].as_compound().unwrap()).slot, Some(0));
+        assert!(ItemStack::from_compound(items[0].as_compound().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn add_unstackable() {
+        let mut inventory = Inventory::from_list(&[], 3, false).unwrap();
+        assert_eq!(inventory.add(ItemStack::new("minecraft:diamond_sword", 2), 1), 0);
+        assert_eq!(inventory.get(0).map(|item| item.count), Some(1));
+        assert_eq!(inventory.get(1).map(|item| item.count), Some(1));
+        assert_eq!(inventory.add(ItemStack::new("minecraft:diamond_sword", 2), 1), 1);
+        assert_eq!(inventory.add(ItemStack::new("minecraft:ender_pearl", 20), 16), 20);
+    }
+
+    #[test]
+    fn write_back_unchanged() {
+        // Fields in the game's order, with one the item model does not know
+        let compound_tags = vec![
+            Tag::new("Count", Choice::Byte(5)),
+            Tag::new("Damage", Choice::Int16(0)),
+            Tag::new("Name", Choice::String("minecraft:apple".to_string())),
+            Tag::new("WasPickedUp", Choice::Byte(0)),
+            Tag::new("Slot", Choice::Byte(3)),
+        ];
+        let list = vec![Choice::Vec(compound_tags.clone())];
+        let mut inventory = Inventory::from_list(&list, 27, false).unwrap();
+        assert_eq!(inventory.to_choice(), Choice::List(TagType::Compound, list.clone()));
+
+        let mut bytes = Vec::new();
+        Tag::new("Items", Choice::List(TagType::Compound, list)).write(&mut bytes).unwrap();
+        let mut written = Vec::new();
+        Tag::new("Items", inventory.to_choice()).write(&mut written).unwrap();
+        assert_eq!(written, bytes);
+
+        // A changed field is updated in place
+        assert_eq!(inventory.remove("minecraft:apple", 2), 2);
+        let Choice::List(_, items) = inventory.to_choice() else { panic!() };
+        let mut expected = compound_tags;
+        expected[0] = Tag::new("Count", Choice::Byte(3));
+        assert_eq!(items, vec![Choice::Vec(expected)]);
+
+        // A new item gets every field
+        let keys: Vec<String> = ItemStack::new("minecraft:stick", 1).to_compound().into_iter().map(|tag| tag.key).collect();
+        assert_eq!(keys, ["Count", "Damage", "Name"]);
+    }
+}
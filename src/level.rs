@@ -0,0 +1,642 @@
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+#[cfg(feature = "json")]
+use crate::nbt::JsonFormat;
+use crate::nbt::{Choice, Diagnostic, ParseMode, SnbtFormat, Tag, TagType};
+
+/// Boolean game rules stored as bytes at the top level of `level.dat`, which
+/// the setters may add when a world does not have them yet.
+pub const BOOL_GAME_RULES: &[&str] = &[
+    "commandblockoutput",
+    "commandblocksenabled",
+    "dodaylightcycle",
+    "doentitydrops",
+    "dofiretick",
+    "doimmediaterespawn",
+    "doinsomnia",
+    "dolimitedcrafting",
+    "domobloot",
+    "domobspawning",
+    "dotiledrops",
+    "doweathercycle",
+    "drowningdamage",
+    "falldamage",
+    "firedamage",
+    "freezedamage",
+    "keepinventory",
+    "mobgriefing",
+    "naturalregeneration",
+    "projectilescanbreakblocks",
+    "pvp",
+    "recipesunlock",
+    "respawnblocksexplode",
+    "sendcommandfeedback",
+    "showbordereffect",
+    "showcoordinates",
+    "showdaysplayed",
+    "showdeathmessages",
+    "showrecipemessages",
+    "showtags",
+    "tntexplodes",
+    "tntexplosiondropdecay",
+];
+
+/// Integer game rules stored at the top level of `level.dat`, which the
+/// setters may add when a world does not have them yet.
+pub const INT_GAME_RULES: &[&str] = &[
+    "functioncommandlimit",
+    "maxcommandchainlength",
+    "playerssleepingpercentage",
+    "randomtickspeed",
+    "spawnradius",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameType {
+    Survival,
+    Creative,
+    Adventure,
+    Default,
+    Spectator,
+}
+
+impl GameType {
+    pub fn from_id(id: i32) -> Option<Self> {
+        match id {
+            0 => Some(GameType::Survival),
+            1 => Some(GameType::Creative),
+            2 => Some(GameType::Adventure),
+            5 => Some(GameType::Default),
+            6 => Some(GameType::Spectator),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        match self {
+            GameType::Survival => 0,
+            GameType::Creative => 1,
+            GameType::Adventure => 2,
+            GameType::Default => 5,
+            GameType::Spectator => 6,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    Peaceful,
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn from_id(id: i32) -> Option<Self> {
+        match id {
+            0 => Some(Difficulty::Peaceful),
+            1 => Some(Difficulty::Easy),
+            2 => Some(Difficulty::Normal),
+            3 => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        match self {
+            Difficulty::Peaceful => 0,
+            Difficulty::Easy => 1,
+            Difficulty::Normal => 2,
+            Difficulty::Hard => 3,
+        }
+    }
+}
+
+/// Length of the version and buffer length fields before the tags.
+const HEADER_LENGTH: u64 = 8;
+
+#[derive(Debug)]
+pub struct LevelData {
+    pub version: i32,
+    pub buffer_length: i32,
+    pub tags: Vec<Tag>,
+}
+
+impl LevelData {
+    pub fn from_file(world_dir: &str) -> Result<Self> {
+        Ok(Self::from_file_with(world_dir, ParseMode::Strict)?.0)
+    }
+
+    /// Reads `level.dat` from a world directory. In lenient mode a corrupt
+    /// file still loads, with the tags read before the corruption and a
+    /// diagnostic of what was lost. A buffer length in the header that
+    /// disagrees with the file is reported as a diagnostic in either mode.
+    pub fn from_file_with(world_dir: &str, mode: ParseMode) -> Result<(Self, Vec<Diagnostic>)> {
+        // Construct file path
+        let file_path = format!("{}/level.dat", world_dir);
+
+        // Open the file in read-only mode
+        let mut file = File::open(&file_path)?;
+
+        // Read the version
+        let mut version_buffer = [0; 4];
+        file.read_exact(&mut version_buffer)?;
+        let version = i32::from_le_bytes(version_buffer);
+
+        // Read the buffer length
+        let mut buffer_length_buffer = [0; 4];
+        file.read_exact(&mut buffer_length_buffer)?;
+        let buffer_length = i32::from_le_bytes(buffer_length_buffer);
+
+        // Read the buffer, with offsets counted from the start of the file.
+        // As in the game, bytes past the buffer length are ignored.
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)?;
+        let mut diagnostics = Vec::new();
+        let declared_length = usize::try_from(buffer_length).ok();
+        if declared_length != Some(buffer.len()) {
+            let message = format!("Header gives a buffer length of {} but {} bytes follow it", buffer_length, buffer.len());
+            let lost_bytes = declared_length.map_or(0, |declared_length| buffer.len().saturating_sub(declared_length));
+            diagnostics.push(Diagnostic { error: Error::invalid_data(message), lost_bytes: lost_bytes as u64 });
+        }
+        let tags_length = declared_length.map_or(buffer.len(), |declared_length| declared_length.min(buffer.len()));
+        let (mut tags, diagnostic) = Tag::parse_all_with(&buffer[..tags_length], HEADER_LENGTH, mode)?;
+        diagnostics.extend(diagnostic);
+        if let Some(end) = tags.iter().position(|tag| tag.tag_type == TagType::End) {
+            tags.truncate(end);
+        }
+
+        let level_data = LevelData {
+            version,
+            buffer_length,
+            tags,
+        };
+        Ok((level_data, diagnostics))
+    }
+
+    pub fn save(&mut self, world_dir: &str) -> Result<()> {
+        // Serialize the tags and recompute the buffer length
+        let (buffer, buffer_length) = write_tags(&self.tags)?;
+        self.buffer_length = buffer_length;
+
+        // Construct file paths
+        let file_path = format!("{}/level.dat", world_dir);
+        let temp_file_path = format!("{}/level.dat.tmp", world_dir);
+        let backup_file_path = format!("{}/level.dat_old", world_dir);
+
+        // Write the header and the buffer to a temporary file
+        let mut temp_file = File::create(&temp_file_path)?;
+        temp_file.write_all(&self.version.to_le_bytes())?;
+        temp_file.write_all(&self.buffer_length.to_le_bytes())?;
+        temp_file.write_all(&buffer)?;
+        temp_file.sync_all()?;
+        drop(temp_file);
+
+        // Keep the previous level.dat as a backup
+        if Path::new(&file_path).exists() {
+            fs::copy(&file_path, &backup_file_path)?;
+        }
+
+        // Atomically replace level.dat with the temporary file
+        Ok(fs::rename(&temp_file_path, &file_path)?)
+    }
+
+    /// Returns the root compound holding all the level fields.
+    pub fn root(&self) -> Option<&Tag> {
+        self.tags.iter().find(|tag| tag.tag_type == TagType::Compound)
+    }
+
+    /// Returns the root compound, creating an empty one if there is none.
+    pub fn root_mut(&mut self) -> &mut Tag {
+        let index = match self.tags.iter().position(|tag| tag.tag_type == TagType::Compound) {
+            Some(index) => index,
+            None => {
+                self.tags.push(Tag::new("", Choice::Vec(Vec::new())));
+                self.tags.len() - 1
+            }
+        };
+        &mut self.tags[index]
+    }
+
+    /// Looks up a value by its path of keys from the root compound.
+    pub fn value(&self, path: &[&str]) -> Option<&Choice> {
+        let mut tag = self.root()?;
+        for key in path {
+            tag = tag.get(key)?;
+        }
+        tag.choice_value.as_ref()
+    }
+
+    /// Sets a value by its path of keys from the root compound, creating the
+    /// intermediate compounds that are missing.
+    pub fn set_value(&mut self, path: &[&str], choice_value: Choice) -> Result<()> {
+        let (last_key, parent_keys) = path.split_last()
+            .ok_or_else(|| Error::invalid_input("Empty key path"))?;
+        let mut tag = self.root_mut();
+        for key in parent_keys {
+            if tag.get(key).is_none() {
+                tag.insert(Tag::new(*key, Choice::Vec(Vec::new())))?;
+            }
+            tag = tag.get_mut(key).unwrap();
+        }
+        tag.insert(Tag::new(*last_key, choice_value))?;
+        Ok(())
+    }
+
+    pub fn level_name(&self) -> Option<&str> {
+        self.value(&["LevelName"])?.as_str()
+    }
+
+    pub fn set_level_name(&mut self, level_name: &str) -> Result<()> {
+        self.set_value(&["LevelName"], Choice::String(level_name.to_string()))
+    }
+
+    pub fn random_seed(&self) -> Option<i64> {
+        self.value(&["RandomSeed"])?.as_i64()
+    }
+
+    pub fn set_random_seed(&mut self, random_seed: i64) -> Result<()> {
+        self.set_value(&["RandomSeed"], Choice::Int64(random_seed))
+    }
+
+    pub fn spawn_x(&self) -> Option<i32> {
+        self.value(&["SpawnX"])?.as_i32()
+    }
+
+    pub fn set_spawn_x(&mut self, spawn_x: i32) -> Result<()> {
+        self.set_value(&["SpawnX"], Choice::Int32(spawn_x))
+    }
+
+    pub fn spawn_y(&self) -> Option<i32> {
+        self.value(&["SpawnY"])?.as_i32()
+    }
+
+    pub fn set_spawn_y(&mut self, spawn_y: i32) -> Result<()> {
+        self.set_value(&["SpawnY"], Choice::Int32(spawn_y))
+    }
+
+    pub fn spawn_z(&self) -> Option<i32> {
+        self.value(&["SpawnZ"])?.as_i32()
+    }
+
+    pub fn set_spawn_z(&mut self, spawn_z: i32) -> Result<()> {
+        self.set_value(&["SpawnZ"], Choice::Int32(spawn_z))
+    }
+
+    pub fn game_type(&self) -> Option<GameType> {
+        GameType::from_id(self.value(&["GameType"])?.as_i32()?)
+    }
+
+    pub fn set_game_type(&mut self, game_type: GameType) -> Result<()> {
+        self.set_value(&["GameType"], Choice::Int32(game_type.id()))
+    }
+
+    pub fn difficulty(&self) -> Option<Difficulty> {
+        Difficulty::from_id(self.value(&["Difficulty"])?.as_i32()?)
+    }
+
+    pub fn set_difficulty(&mut self, difficulty: Difficulty) -> Result<()> {
+        self.set_value(&["Difficulty"], Choice::Int32(difficulty.id()))
+    }
+
+    pub fn time(&self) -> Option<i64> {
+        self.value(&["Time"])?.as_i64()
+    }
+
+    pub fn set_time(&mut self, time: i64) -> Result<()> {
+        self.set_value(&["Time"], Choice::Int64(time))
+    }
+
+    pub fn last_played(&self) -> Option<i64> {
+        self.value(&["LastPlayed"])?.as_i64()
+    }
+
+    pub fn set_last_played(&mut self, last_played: i64) -> Result<()> {
+        self.set_value(&["LastPlayed"], Choice::Int64(last_played))
+    }
+
+    pub fn storage_version(&self) -> Option<i32> {
+        self.value(&["StorageVersion"])?.as_i32()
+    }
+
+    pub fn set_storage_version(&mut self, storage_version: i32) -> Result<()> {
+        self.set_value(&["StorageVersion"], Choice::Int32(storage_version))
+    }
+
+    /// Returns a boolean game rule, such as `keepInventory`. Game rule keys
+    /// are all lowercase in `level.dat`, so the name is lowercased first.
+    pub fn bool_game_rule(&self, name: &str) -> Option<bool> {
+        Some(self.value(&[&name.to_lowercase()])?.as_byte()? != 0)
+    }
+
+    /// Sets a boolean game rule the world already has, or one of
+    /// `BOOL_GAME_RULES`.
+    pub fn set_bool_game_rule(&mut self, name: &str, value: bool) -> Result<()> {
+        let key = self.game_rule_key(name, BOOL_GAME_RULES, TagType::Byte, "boolean")?;
+        self.set_value(&[&key], Choice::Byte(value as i8))
+    }
+
+    /// Returns an integer game rule, such as `randomTickSpeed`.
+    pub fn int_game_rule(&self, name: &str) -> Option<i32> {
+        self.value(&[&name.to_lowercase()])?.as_i32()
+    }
+
+    /// Sets an integer game rule the world already has, or one of
+    /// `INT_GAME_RULES`.
+    pub fn set_int_game_rule(&mut self, name: &str, value: i32) -> Result<()> {
+        let key = self.game_rule_key(name, INT_GAME_RULES, TagType::Int32, "integer")?;
+        self.set_value(&[&key], Choice::Int32(value))
+    }
+
+    /// Returns the `level.dat` key of a `kind` game rule, whose values are of
+    /// type `tag_type`. Keys missing from `level.dat` must be one of
+    /// `game_rules`.
+    fn game_rule_key(&self, name: &str, game_rules: &[&str], tag_type: TagType, kind: &str) -> Result<String> {
+        let key = name.to_lowercase();
+        match self.value(&[&key]) {
+            Some(value) if value.tag_type() == tag_type => Ok(key),
+            Some(value) => Err(Error::invalid_input(format!("Game rule {} holds a {:?}, not a {} value", name, value.tag_type(), kind))),
+            None if game_rules.contains(&key.as_str()) => Ok(key),
+            None => Err(Error::invalid_input(format!("Not a known {} game rule: {}", kind, name))),
+        }
+    }
+
+    /// Returns whether an experimental toggle from the `experiments` compound
+    /// is enabled.
+    pub fn experiment(&self, name: &str) -> Option<bool> {
+        Some(self.value(&["experiments", name])?.as_byte()? != 0)
+    }
+
+    pub fn set_experiment(&mut self, name: &str, enabled: bool) -> Result<()> {
+        self.set_value(&["experiments", name], Choice::Byte(enabled as i8))
+    }
+
+    /// Returns a boolean flag from the `abilities` compound, such as `mayfly`
+    /// or `instabuild`.
+    pub fn ability(&self, name: &str) -> Option<bool> {
+        Some(self.value(&["abilities", name])?.as_byte()? != 0)
+    }
+
+    pub fn set_ability(&mut self, name: &str, value: bool) -> Result<()> {
+        self.set_value(&["abilities", name], Choice::Byte(value as i8))
+    }
+
+    pub fn fly_speed(&self) -> Option<f32> {
+        self.value(&["abilities", "flySpeed"])?.as_f32()
+    }
+
+    pub fn set_fly_speed(&mut self, fly_speed: f32) -> Result<()> {
+        self.set_value(&["abilities", "flySpeed"], Choice::Float32(fly_speed))
+    }
+
+    pub fn walk_speed(&self) -> Option<f32> {
+        self.value(&["abilities", "walkSpeed"])?.as_f32()
+    }
+
+    pub fn set_walk_speed(&mut self, walk_speed: f32) -> Result<()> {
+        self.set_value(&["abilities", "walkSpeed"], Choice::Float32(walk_speed))
+    }
+
+    /// Converts the level data to JSON. The plain format gives the version
+    /// and the root compound as an object; the typed format keeps every tag
+    /// so it can be read back with [`LevelData::from_json`].
+    #[cfg(feature = "json")]
+    pub fn to_json(&self, format: JsonFormat) -> serde_json::Value {
+        let mut object = serde_json::Map::new();
+        object.insert("version".to_string(), self.version.into());
+        match format {
+            JsonFormat::Plain => {
+                let root = self.root().map_or(serde_json::Value::Null, |tag| tag.to_json(format));
+                object.insert("data".to_string(), root);
+            }
+            JsonFormat::Typed => {
+                let tags = self.tags.iter().map(|tag| tag.to_json(format)).collect();
+                object.insert("tags".to_string(), serde_json::Value::Array(tags));
+            }
+        }
+        serde_json::Value::Object(object)
+    }
+
+    /// Reads level data back from its typed JSON form. The buffer length is
+    /// recomputed when saving.
+    #[cfg(feature = "json")]
+    pub fn from_json(value: &serde_json::Value) -> Result<Self> {
+        let version = value.get("version")
+            .and_then(|version| version.as_i64())
+            .and_then(|version| i32::try_from(version).ok())
+            .ok_or_else(|| Error::invalid_data("Missing level data version"))?;
+        let tags = value.get("tags")
+            .and_then(|tags| tags.as_array())
+            .ok_or_else(|| Error::invalid_data("Missing level data tags"))?
+            .iter()
+            .map(Tag::from_json)
+            .collect::<Result<Vec<Tag>>>()?;
+
+        let (_, buffer_length) = write_tags(&tags)?;
+        Ok(LevelData {
+            version,
+            buffer_length,
+            tags,
+        })
+    }
+
+    pub fn print(&self) {
+        println!("Version: {}", self.version);
+        println!("Buffer Length: {}", self.buffer_length);
+        println!("Tags:");
+        for tag in &self.tags {
+            println!("{}", tag.to_snbt(SnbtFormat::Pretty));
+        }
+    }
+}
+
+/// Serializes the tags of `level.dat`, returning them with the buffer length
+/// of the header.
+fn write_tags(tags: &[Tag]) -> Result<(Vec<u8>, i32)> {
+    let mut buffer = Vec::new();
+    for tag in tags {
+        tag.write(&mut buffer)?;
+    }
+    let buffer_length = i32::try_from(buffer.len())
+        .map_err(|_| Error::invalid_input(format!("Level data too long: {}", buffer.len())))?;
+    Ok((buffer, buffer_length))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::nbt::tests::{sample_level_dat, LEVEL_DAT_FIXTURE};
+
+    /// A world directory under the system temporary directory, removed when
+    /// dropped.
+    struct WorldDir(PathBuf);
+
+    impl WorldDir {
+        fn new(name: &str, level_dat: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!("minecraft-rust-{}-{}", name, std::process::id()));
+            fs::create_dir_all(&path).unwrap();
+            fs::write(path.join("level.dat"), level_dat).unwrap();
+            WorldDir(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+
+        fn read(&self, file_name: &str) -> Vec<u8> {
+            fs::read(self.0.join(file_name)).unwrap()
+        }
+    }
+
+    impl Drop for WorldDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn header_buffer_length(level_dat: &[u8]) -> usize {
+        i32::from_le_bytes(level_dat[4..8].try_into().unwrap()) as usize
+    }
+
+    #[test]
+    fn save_keeps_backup() {
+        let original = sample_level_dat();
+        let world_dir = WorldDir::new("save", &original);
+
+        let mut level_data = LevelData::from_file(world_dir.path()).unwrap();
+        level_data.set_level_name("Renamed World").unwrap();
+        level_data.save(world_dir.path()).unwrap();
+        let first_save = world_dir.read("level.dat");
+        assert_eq!(world_dir.read("level.dat_old"), original);
+        assert_eq!(header_buffer_length(&first_save), first_save.len() - 8);
+        assert_eq!(level_data.buffer_length as usize, first_save.len() - 8);
+        assert_eq!(first_save[..4], original[..4]);
+
+        level_data.set_level_name("W").unwrap();
+        level_data.save(world_dir.path()).unwrap();
+        let second_save = world_dir.read("level.dat");
+        assert_eq!(world_dir.read("level.dat_old"), first_save);
+        assert_eq!(header_buffer_length(&second_save), second_save.len() - 8);
+        assert!(!world_dir.0.join("level.dat.tmp").exists());
+
+        let reloaded = LevelData::from_file(world_dir.path()).unwrap();
+        assert_eq!(reloaded.level_name(), Some("W"));
+        assert_eq!(reloaded.tags, level_data.tags);
+    }
+
+    #[test]
+    fn setters_create_compounds() {
+        let mut level_data = LevelData {
+            version: 10,
+            buffer_length: 0,
+            tags: Vec::new(),
+        };
+        level_data.set_fly_speed(0.1).unwrap();
+        level_data.set_experiment("gametest", true).unwrap();
+        level_data.set_value(&["a", "b", "c"], Choice::Int32(3)).unwrap();
+        level_data.set_level_name("World").unwrap();
+
+        assert_eq!(level_data.tags.len(), 1);
+        assert_eq!(level_data.fly_speed(), Some(0.1));
+        assert_eq!(level_data.experiment("gametest"), Some(true));
+        assert_eq!(level_data.value(&["a", "b", "c"]), Some(&Choice::Int32(3)));
+        assert!(level_data.value(&["a", "b"]).and_then(Choice::as_compound).is_some());
+        assert_eq!(level_data.level_name(), Some("World"));
+        assert_eq!(level_data.ability("mayfly"), None);
+
+        assert!(level_data.set_value(&[], Choice::Byte(0)).is_err());
+        assert!(level_data.set_value(&["LevelName", "x"], Choice::Byte(0)).is_err());
+        assert_eq!(level_data.level_name(), Some("World"));
+
+        let mut written = Vec::new();
+        level_data.tags[0].write(&mut written).unwrap();
+        assert_eq!(Tag::parse_all(&written).unwrap(), level_data.tags);
+    }
+
+    #[test]
+    fn fixture() {
+        let world_dir = WorldDir::new("fixture", LEVEL_DAT_FIXTURE);
+        let mut level_data = LevelData::from_file(world_dir.path()).unwrap();
+        assert_eq!(level_data.level_name(), Some("Bedrock level"));
+        assert_eq!(level_data.random_seed(), Some(-4_011_961_473_290_093_582));
+        assert_eq!(level_data.storage_version(), Some(10));
+        assert_eq!(level_data.bool_game_rule("showRecipeMessages"), Some(true));
+        assert_eq!(level_data.int_game_rule("functionCommandLimit"), Some(10_000));
+        assert_eq!(level_data.experiment("experiments_ever_used"), Some(false));
+
+        level_data.save(world_dir.path()).unwrap();
+        assert_eq!(world_dir.read("level.dat"), LEVEL_DAT_FIXTURE);
+    }
+
+    #[test]
+    fn game_rules() {
+        let mut level_data = LevelData {
+            version: 10,
+            buffer_length: 0,
+            tags: Tag::parse_all(&sample_level_dat()[8..]).unwrap(),
+        };
+        assert_eq!(level_data.bool_game_rule("commandBlockOutput"), Some(true));
+        assert_eq!(level_data.bool_game_rule("keepInventory"), None);
+
+        level_data.set_bool_game_rule("keepInventory", true).unwrap();
+        level_data.set_int_game_rule("randomTickSpeed", 3).unwrap();
+        assert_eq!(level_data.value(&["keepinventory"]), Some(&Choice::Byte(1)));
+        assert_eq!(level_data.bool_game_rule("KEEPINVENTORY"), Some(true));
+        assert_eq!(level_data.int_game_rule("randomtickspeed"), Some(3));
+
+        // Names are checked against the game rules of the accessor's type
+        let err = level_data.set_bool_game_rule("randomTickSpeed", true).unwrap_err();
+        assert_eq!(err.to_string(), "Game rule randomTickSpeed holds a Int32, not a boolean value");
+        assert!(level_data.set_int_game_rule("keepInventory", 1).is_err());
+        let err = level_data.set_bool_game_rule("LevelName", true).unwrap_err();
+        assert_eq!(err.to_string(), "Not a known boolean game rule: LevelName");
+        assert!(level_data.set_int_game_rule("newGameRule", 1).is_err());
+        assert_eq!(level_data.int_game_rule("keepInventory"), None);
+        assert_eq!(level_data.level_name(), Some("World"));
+
+        // Rules the world already has are read and written even when unknown
+        level_data.set_value(&["shownewrule"], Choice::Byte(0)).unwrap();
+        level_data.set_value(&["newlimit"], Choice::Int32(5)).unwrap();
+        assert_eq!(level_data.bool_game_rule("showNewRule"), Some(false));
+        assert_eq!(level_data.int_game_rule("newLimit"), Some(5));
+        level_data.set_bool_game_rule("showNewRule", true).unwrap();
+        level_data.set_int_game_rule("newLimit", 6).unwrap();
+        assert_eq!(level_data.bool_game_rule("shownewrule"), Some(true));
+        assert_eq!(level_data.int_game_rule("newlimit"), Some(6));
+        assert!(level_data.set_int_game_rule("showNewRule", 1).is_err());
+
+        for name in ["showRecipeMessages", "recipesUnlock", "doLimitedCrafting", "projectilesCanBreakBlocks", "tntExplosionDropDecay", "showBorderEffect", "showDaysPlayed"] {
+            level_data.set_bool_game_rule(name, false).unwrap();
+            assert_eq!(level_data.bool_game_rule(name), Some(false), "{}", name);
+        }
+    }
+
+    #[test]
+    fn buffer_length_mismatch() {
+        let original = sample_level_dat();
+        let expected = LevelData::from_file(WorldDir::new("exact", &original).path()).unwrap();
+
+        // Trailing bytes past the buffer length are ignored
+        let mut trailing = original.clone();
+        trailing.push(0xAA);
+        let world_dir = WorldDir::new("trailing", &trailing);
+        let level_data = LevelData::from_file(world_dir.path()).unwrap();
+        assert_eq!(level_data.tags, expected.tags);
+        let (_, diagnostics) = LevelData::from_file_with(world_dir.path(), ParseMode::Strict).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].lost_bytes, 1);
+
+        // A truncated file is an error in strict mode only
+        let world_dir = WorldDir::new("truncated", &original[..original.len() - 10]);
+        assert!(LevelData::from_file(world_dir.path()).is_err());
+        let (level_data, diagnostics) = LevelData::from_file_with(world_dir.path(), ParseMode::Lenient).unwrap();
+        assert_eq!(level_data.level_name(), Some("World"));
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[1].error.path(), Some("abilities"));
+    }
+}
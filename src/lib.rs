@@ -0,0 +1,21 @@
+//! Minecraft World Analyzer
+//!
+//! Reading and writing of Bedrock Edition worlds: little-endian NBT (`nbt`),
+//! the `level.dat` file (`level`), chunk data (`chunk`), block states
+//! (`block`), entities (`entity`),
+//! players (`player`), their items (`inventory`), the LevelDB world database
+//! (`db`) and the dimensions of a world (`world`). Access to LevelDB itself
+//! is behind the `leveldb` feature. Failures are reported as [`Error`].
+
+pub mod block;
+pub mod chunk;
+pub mod db;
+pub mod entity;
+pub mod error;
+pub mod inventory;
+pub mod level;
+pub mod nbt;
+pub mod player;
+pub mod world;
+
+pub use error::{Error, Result};
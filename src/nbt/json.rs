@@ -0,0 +1,384 @@
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Number, Value};
+
+use crate::error::{Error, Result};
+use crate::nbt::{Choice, Tag, TagType};
+
+/// Layout of JSON output: plain values for people and scripts, or typed values
+/// that keep every NBT type and the order of compound entries so they can be
+/// converted back to identical binary NBT.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JsonFormat {
+    Plain,
+    Typed,
+}
+
+impl Choice {
+    /// Converts the value to JSON. In the plain format compounds become
+    /// objects and non-finite floats become `null`; in the typed format every
+    /// value is an object with a `type` and a `value`, or the `bits` of a NaN
+    /// other than the canonical one.
+    pub fn to_json(&self, format: JsonFormat) -> Value {
+        match format {
+            JsonFormat::Plain => plain_value(self),
+            JsonFormat::Typed => {
+                let mut object = Map::new();
+                write_typed(&mut object, self);
+                Value::Object(object)
+            }
+        }
+    }
+
+    /// Reads a value back from its typed JSON form.
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let object = value.as_object()
+            .ok_or_else(|| invalid_json(format!("Expected a typed value object: {}", value)))?;
+        match read_typed(object)? {
+            Some(choice_value) => Ok(choice_value),
+            None => Err(invalid_json("Unexpected end tag".to_string())),
+        }
+    }
+}
+
+impl Tag {
+    /// Converts the tag to JSON. The plain format gives `{key: value}`, or the
+    /// value alone for root tags without a key; the typed format gives
+    /// `{"key", "type", "value"}`.
+    pub fn to_json(&self, format: JsonFormat) -> Value {
+        match format {
+            JsonFormat::Plain => {
+                let value = self.choice_value.as_ref().map_or(Value::Null, plain_value);
+                if self.key.is_empty() {
+                    return value;
+                }
+                let mut object = Map::new();
+                object.insert(self.key.clone(), value);
+                Value::Object(object)
+            }
+            JsonFormat::Typed => typed_tag(self),
+        }
+    }
+
+    /// Reads a tag back from its typed JSON form.
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let object = value.as_object()
+            .ok_or_else(|| invalid_json(format!("Expected a typed tag object: {}", value)))?;
+        let key = match object.get("key") {
+            Some(Value::String(key)) => key.clone(),
+            Some(key) => return Err(invalid_json(format!("Invalid tag key: {}", key))),
+            None => String::new(),
+        };
+        match read_typed(object)? {
+            Some(choice_value) => Ok(Tag::new(key, choice_value)),
+            None => Ok(Tag {
+                tag_type: TagType::End,
+                key,
+                choice_value: None,
+            }),
+        }
+    }
+}
+
+/// Serializes the typed JSON form, which deserializes back losslessly.
+impl Serialize for Choice {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.to_json(JsonFormat::Typed).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Choice {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        Choice::from_json(&Value::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+/// Serializes the typed JSON form, which deserializes back losslessly.
+impl Serialize for Tag {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.to_json(JsonFormat::Typed).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Tag {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        Tag::from_json(&Value::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+fn invalid_json(message: String) -> Error {
+    Error::InvalidData(message)
+}
+
+fn type_name(tag_type: &TagType) -> &'static str {
+    match tag_type {
+        TagType::End => "end",
+        TagType::Byte => "byte",
+        TagType::Int16 => "short",
+        TagType::Int32 => "int",
+        TagType::Int64 => "long",
+        TagType::Float => "float",
+        TagType::Double => "double",
+        TagType::ByteArray => "byte_array",
+        TagType::String => "string",
+        TagType::List => "list",
+        TagType::Compound => "compound",
+        TagType::IntArray => "int_array",
+        TagType::LongArray => "long_array",
+    }
+}
+
+fn type_from_name(name: &str) -> Result<TagType> {
+    match name {
+        "end" => Ok(TagType::End),
+        "byte" => Ok(TagType::Byte),
+        "short" => Ok(TagType::Int16),
+        "int" => Ok(TagType::Int32),
+        "long" => Ok(TagType::Int64),
+        "float" => Ok(TagType::Float),
+        "double" => Ok(TagType::Double),
+        "byte_array" => Ok(TagType::ByteArray),
+        "string" => Ok(TagType::String),
+        "list" => Ok(TagType::List),
+        "compound" => Ok(TagType::Compound),
+        "int_array" => Ok(TagType::IntArray),
+        "long_array" => Ok(TagType::LongArray),
+        _ => Err(invalid_json(format!("Unknown tag type: {}", name))),
+    }
+}
+
+/// Floats are written through their shortest decimal form, so `0.1f` stays
+/// `0.1` rather than widening to `0.10000000149011612`.
+fn plain_f32(value: f32) -> Value {
+    Number::from_f64(value.to_string().parse().unwrap()).map_or(Value::Null, Value::Number)
+}
+
+fn plain_f64(value: f64) -> Value {
+    Number::from_f64(value).map_or(Value::Null, Value::Number)
+}
+
+fn plain_value(choice_value: &Choice) -> Value {
+    match choice_value {
+        Choice::Byte(value) => Value::from(*value),
+        Choice::Int16(value) => Value::from(*value),
+        Choice::Int32(value) => Value::from(*value),
+        Choice::Int64(value) => Value::from(*value),
+        Choice::Float32(value) => plain_f32(*value),
+        Choice::Float64(value) => plain_f64(*value),
+        Choice::ByteArray(values) => Value::from(values.clone()),
+        Choice::String(value) => Value::from(value.clone()),
+        Choice::List(_, values) => Value::Array(values.iter().map(plain_value).collect()),
+        Choice::Vec(compound_tags) => Value::Object(compound_tags.iter()
+            .map(|child_tag| (child_tag.key.clone(), child_tag.choice_value.as_ref().map_or(Value::Null, plain_value)))
+            .collect()),
+        Choice::IntArray(values) => Value::from(values.clone()),
+        Choice::LongArray(values) => Value::from(values.clone()),
+    }
+}
+
+/// JSON has no NaN or infinities, so the typed format spells them as strings.
+/// `"NaN"` reads back as the canonical NaN, and the other NaNs are written as
+/// their bits instead, by `write_typed`.
+fn typed_float(value: f64, shortest: Value) -> Value {
+    if value.is_nan() {
+        Value::from("NaN")
+    } else if value.is_infinite() {
+        Value::from(if value > 0.0 { "Infinity" } else { "-Infinity" })
+    } else {
+        shortest
+    }
+}
+
+fn typed_tag(tag: &Tag) -> Value {
+    let mut object = Map::new();
+    object.insert("key".to_string(), Value::from(tag.key.clone()));
+    match &tag.choice_value {
+        Some(choice_value) => write_typed(&mut object, choice_value),
+        None => {
+            object.insert("type".to_string(), Value::from(type_name(&TagType::End)));
+        }
+    }
+    Value::Object(object)
+}
+
+fn write_typed(object: &mut Map<String, Value>, choice_value: &Choice) {
+    let bits = match choice_value {
+        Choice::Float32(value) if value.is_nan() && value.to_bits() != f32::NAN.to_bits() => Some(value.to_bits() as u64),
+        Choice::Float64(value) if value.is_nan() && value.to_bits() != f64::NAN.to_bits() => Some(value.to_bits()),
+        _ => None,
+    };
+    if let Some(bits) = bits {
+        object.insert("type".to_string(), Value::from(type_name(&choice_value.tag_type())));
+        object.insert("bits".to_string(), Value::from(bits));
+        return;
+    }
+
+    let value = match choice_value {
+        Choice::Float32(value) => typed_float(*value as f64, plain_f32(*value)),
+        Choice::Float64(value) => typed_float(*value, plain_f64(*value)),
+        Choice::List(element_type, values) => {
+            object.insert("element_type".to_string(), Value::from(type_name(element_type)));
+            Value::Array(values.iter().map(|value| value.to_json(JsonFormat::Typed)).collect())
+        }
+        Choice::Vec(compound_tags) => Value::Array(compound_tags.iter().map(typed_tag).collect()),
+        _ => plain_value(choice_value),
+    };
+    object.insert("type".to_string(), Value::from(type_name(&choice_value.tag_type())));
+    object.insert("value".to_string(), value);
+}
+
+fn read_integer<T: TryFrom<i64>>(value: &Value) -> Result<T> {
+    value.as_i64()
+        .and_then(|integer| T::try_from(integer).ok())
+        .ok_or_else(|| invalid_json(format!("Invalid integer: {}", value)))
+}
+
+fn read_float(value: &Value) -> Result<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(name) if name == "NaN" => Some(f64::NAN),
+        Value::String(name) if name == "Infinity" => Some(f64::INFINITY),
+        Value::String(name) if name == "-Infinity" => Some(f64::NEG_INFINITY),
+        _ => None,
+    }.ok_or_else(|| invalid_json(format!("Invalid float: {}", value)))
+}
+
+fn read_bits<T: TryFrom<u64>>(value: &Value) -> Result<T> {
+    value.as_u64()
+        .and_then(|bits| T::try_from(bits).ok())
+        .ok_or_else(|| invalid_json(format!("Invalid float bits: {}", value)))
+}
+
+fn read_array<T: TryFrom<i64>>(value: &Value) -> Result<Vec<T>> {
+    value.as_array()
+        .ok_or_else(|| invalid_json(format!("Expected an array: {}", value)))?
+        .iter()
+        .map(read_integer)
+        .collect()
+}
+
+/// Reads the `type` and `value` of a typed object; end tags have no value.
+fn read_typed(object: &Map<String, Value>) -> Result<Option<Choice>> {
+    let tag_type = match object.get("type") {
+        Some(Value::String(name)) => type_from_name(name)?,
+        _ => return Err(invalid_json(format!("Missing tag type: {}", Value::Object(object.clone())))),
+    };
+    if tag_type == TagType::End {
+        return Ok(None);
+    }
+    if let Some(bits) = object.get("bits") {
+        return match tag_type {
+            TagType::Float => Ok(Some(Choice::Float32(f32::from_bits(read_bits(bits)?)))),
+            TagType::Double => Ok(Some(Choice::Float64(f64::from_bits(read_bits(bits)?)))),
+            _ => Err(invalid_json(format!("Bits given for a {} tag", type_name(&tag_type)))),
+        };
+    }
+    let value = object.get("value")
+        .ok_or_else(|| invalid_json(format!("Missing value for {} tag", type_name(&tag_type))))?;
+
+    let choice_value = match tag_type {
+        TagType::End => unreachable!(),
+        TagType::Byte => Choice::Byte(read_integer(value)?),
+        TagType::Int16 => Choice::Int16(read_integer(value)?),
+        TagType::Int32 => Choice::Int32(read_integer(value)?),
+        TagType::Int64 => Choice::Int64(read_integer(value)?),
+        TagType::Float => Choice::Float32(match read_float(value)? {
+            value if value.is_nan() => f32::NAN,
+            value => value as f32,
+        }),
+        TagType::Double => Choice::Float64(read_float(value)?),
+        TagType::ByteArray => Choice::ByteArray(read_array(value)?),
+        TagType::String => Choice::String(value.as_str()
+            .ok_or_else(|| invalid_json(format!("Expected a string: {}", value)))?
+            .to_string()),
+        TagType::List => {
+            let element_type = match object.get("element_type") {
+                Some(Value::String(name)) => type_from_name(name)?,
+                _ => return Err(invalid_json("Missing list element type".to_string())),
+            };
+            let values = value.as_array()
+                .ok_or_else(|| invalid_json(format!("Expected an array: {}", value)))?
+                .iter()
+                .map(Choice::from_json)
+                .collect::<Result<Vec<Choice>>>()?;
+            if let Some(mismatch) = values.iter().find(|value| value.tag_type() != element_type) {
+                return Err(invalid_json(format!(
+                    "List element of type {} in a list of {}", type_name(&mismatch.tag_type()), type_name(&element_type),
+                )));
+            }
+            Choice::List(element_type, values)
+        }
+        TagType::Compound => Choice::Vec(value.as_array()
+            .ok_or_else(|| invalid_json(format!("Expected an array: {}", value)))?
+            .iter()
+            .map(Tag::from_json)
+            .collect::<Result<Vec<Tag>>>()?),
+        TagType::IntArray => Choice::IntArray(read_array(value)?),
+        TagType::LongArray => Choice::LongArray(read_array(value)?),
+    };
+    Ok(Some(choice_value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tests::sample_level_dat;
+
+    #[test]
+    fn typed_round_trip() {
+        let tags = Tag::parse_all(&sample_level_dat()[8..]).unwrap();
+        let json = serde_json::to_string(&tags).unwrap();
+        let parsed: Vec<Tag> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, tags);
+
+        let mut original = Vec::new();
+        let mut round_tripped = Vec::new();
+        for (tag, parsed_tag) in tags.iter().zip(&parsed) {
+            tag.write(&mut original).unwrap();
+            parsed_tag.write(&mut round_tripped).unwrap();
+        }
+        assert_eq!(round_tripped, original);
+
+        let nan = Choice::from_json(&Choice::Float32(f32::NAN).to_json(JsonFormat::Typed)).unwrap();
+        assert_eq!(nan.as_f32().map(f32::to_bits), Some(f32::NAN.to_bits()));
+
+        // Other NaNs are written as their bits
+        let nans = [
+            Choice::Float32(f32::from_bits(0x7fc0_0001)),
+            Choice::Float32(f32::from_bits(0xffc0_0000)),
+            Choice::Float64(f64::from_bits(0xfff8_0000_0000_0001)),
+        ];
+        for nan in nans {
+            let json = nan.to_json(JsonFormat::Typed);
+            assert!(json.get("value").is_none(), "{}", json);
+            let mut written = Vec::new();
+            Choice::from_json(&json).unwrap().write(&mut written).unwrap();
+            let mut original = Vec::new();
+            nan.write(&mut original).unwrap();
+            assert_eq!(written, original, "{}", json);
+        }
+        assert_eq!(
+            Choice::Float32(f32::from_bits(0x7fc0_0001)).to_json(JsonFormat::Typed).to_string(),
+            r#"{"type":"float","bits":2143289345}"#
+        );
+        assert!(Choice::from_json(&serde_json::json!({"type": "float", "bits": 1u64 << 32})).is_err());
+        assert!(Choice::from_json(&serde_json::json!({"type": "int", "bits": 1})).is_err());
+    }
+
+    #[test]
+    fn plain_values() {
+        let tag = Tag::new("abilities", Choice::Vec(vec![
+            Tag::new("flySpeed", Choice::Float32(0.05)),
+            Tag::new("mayfly", Choice::Byte(1)),
+            Tag::new("pos", Choice::List(TagType::Int32, vec![Choice::Int32(1), Choice::Int32(-2)])),
+        ]));
+        assert_eq!(
+            tag.to_json(JsonFormat::Plain).to_string(),
+            r#"{"abilities":{"flySpeed":0.05,"mayfly":1,"pos":[1,-2]}}"#
+        );
+        assert_eq!(
+            Choice::Int16(3).to_json(JsonFormat::Typed).to_string(),
+            r#"{"type":"short","value":3}"#
+        );
+    }
+}
@@ -0,0 +1,869 @@
+#[cfg(feature = "json")]
+pub mod json;
+pub mod snbt;
+
+use std::fmt;
+use std::io::{Read, Write};
+
+use crate::error::{Error, Result};
+
+#[cfg(feature = "json")]
+pub use json::JsonFormat;
+pub use snbt::{SnbtError, SnbtFormat};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagType {
+    End,
+    Byte,
+    Int16,
+    Int32,
+    Int64,
+    Float,
+    Double,
+    ByteArray,
+    String,
+    List,
+    Compound,
+    IntArray,
+    LongArray,
+}
+
+impl TagType {
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self> {
+        NbtReader::new(reader, 0, ParseMode::Strict).read_tag_type()
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(TagType::End),
+            1 => Some(TagType::Byte),
+            2 => Some(TagType::Int16),
+            3 => Some(TagType::Int32),
+            4 => Some(TagType::Int64),
+            5 => Some(TagType::Float),
+            6 => Some(TagType::Double),
+            7 => Some(TagType::ByteArray),
+            8 => Some(TagType::String),
+            9 => Some(TagType::List),
+            10 => Some(TagType::Compound),
+            11 => Some(TagType::IntArray),
+            12 => Some(TagType::LongArray),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        match self {
+            TagType::End => 0,
+            TagType::Byte => 1,
+            TagType::Int16 => 2,
+            TagType::Int32 => 3,
+            TagType::Int64 => 4,
+            TagType::Float => 5,
+            TagType::Double => 6,
+            TagType::ByteArray => 7,
+            TagType::String => 8,
+            TagType::List => 9,
+            TagType::Compound => 10,
+            TagType::IntArray => 11,
+            TagType::LongArray => 12,
+        }
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        Ok(writer.write_all(&[self.id()])?)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Choice {
+    Byte(i8),
+    Int16(i16),
+    Int32(i32),
+    Int64(i64),
+    Float32(f32),
+    Float64(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(TagType, Vec<Choice>),
+    Vec(Vec<Tag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Choice {
+    pub fn parse<R: Read>(reader: &mut R, tag_type: TagType) -> Result<Self> {
+        NbtReader::new(reader, 0, ParseMode::Strict).read_choice(tag_type)
+    }
+
+    pub fn tag_type(&self) -> TagType {
+        match self {
+            Choice::Byte(_) => TagType::Byte,
+            Choice::Int16(_) => TagType::Int16,
+            Choice::Int32(_) => TagType::Int32,
+            Choice::Int64(_) => TagType::Int64,
+            Choice::Float32(_) => TagType::Float,
+            Choice::Float64(_) => TagType::Double,
+            Choice::ByteArray(_) => TagType::ByteArray,
+            Choice::String(_) => TagType::String,
+            Choice::List(_, _) => TagType::List,
+            Choice::Vec(_) => TagType::Compound,
+            Choice::IntArray(_) => TagType::IntArray,
+            Choice::LongArray(_) => TagType::LongArray,
+        }
+    }
+
+    pub fn as_byte(&self) -> Option<i8> {
+        match self {
+            Choice::Byte(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i16(&self) -> Option<i16> {
+        match self {
+            Choice::Int16(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i32(&self) -> Option<i32> {
+        match self {
+            Choice::Int32(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Choice::Int64(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            Choice::Float32(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Choice::Float64(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Choice::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Choice]> {
+        match self {
+            Choice::List(_, values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Returns the values of a list of exactly `N` floats, such as a position.
+    pub fn as_floats<const N: usize>(&self) -> Option<[f32; N]> {
+        let values = self.as_list()?;
+        if values.len() != N {
+            return None;
+        }
+        let mut floats = [0.0; N];
+        for (float, value) in floats.iter_mut().zip(values) {
+            *float = value.as_f32()?;
+        }
+        Some(floats)
+    }
+
+    pub fn as_compound(&self) -> Option<&[Tag]> {
+        match self {
+            Choice::Vec(compound_tags) => Some(compound_tags),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound_mut(&mut self) -> Option<&mut Vec<Tag>> {
+        match self {
+            Choice::Vec(compound_tags) => Some(compound_tags),
+            _ => None,
+        }
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        match self {
+            Choice::Byte(value) => Ok(writer.write_all(&value.to_le_bytes())?),
+            Choice::Int16(value) => Ok(writer.write_all(&value.to_le_bytes())?),
+            Choice::Int32(value) => Ok(writer.write_all(&value.to_le_bytes())?),
+            Choice::Int64(value) => Ok(writer.write_all(&value.to_le_bytes())?),
+            Choice::Float32(value) => Ok(writer.write_all(&value.to_le_bytes())?),
+            Choice::Float64(value) => Ok(writer.write_all(&value.to_le_bytes())?),
+            Choice::ByteArray(values) => {
+                write_array_length(writer, values.len())?;
+                let bytes: Vec<u8> = values.iter().map(|&byte| byte as u8).collect();
+                Ok(writer.write_all(&bytes)?)
+            }
+            Choice::String(value) => write_string(writer, value),
+            Choice::List(element_type, values) => {
+                // Elements of another type would not parse back
+                if let Some(value) = values.iter().find(|value| value.tag_type() != *element_type) {
+                    return Err(Error::invalid_input(format!(
+                        "List of {:?} holds a {:?}", element_type, value.tag_type(),
+                    )));
+                }
+                element_type.write(writer)?;
+                write_array_length(writer, values.len())?;
+                for value in values {
+                    value.write(writer)?;
+                }
+                Ok(())
+            }
+            Choice::Vec(compound_tags) => {
+                for child_tag in compound_tags {
+                    child_tag.write(writer)?;
+                }
+                TagType::End.write(writer)
+            }
+            Choice::IntArray(values) => {
+                write_array_length(writer, values.len())?;
+                for value in values {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+                Ok(())
+            }
+            Choice::LongArray(values) => {
+                write_array_length(writer, values.len())?;
+                for value in values {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn write_array_length<W: Write>(writer: &mut W, length: usize) -> Result<()> {
+    let length = i32::try_from(length)
+        .map_err(|_| Error::invalid_input(format!("Array too long: {}", length)))?;
+    Ok(writer.write_all(&length.to_le_bytes())?)
+}
+
+fn write_string<W: Write>(writer: &mut W, value: &str) -> Result<()> {
+    let length = u16::try_from(value.len())
+        .map_err(|_| Error::invalid_input(format!("String too long: {}", value.len())))?;
+    writer.write_all(&length.to_le_bytes())?;
+    Ok(writer.write_all(value.as_bytes())?)
+}
+
+/// How parsing handles corrupt NBT.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Fail on the first error.
+    #[default]
+    Strict,
+    /// Stop at the first error but keep every tag read before it, including
+    /// the compounds and lists it cut short, and report it as a diagnostic.
+    Lenient,
+}
+
+/// The error that ended lenient parsing and the data lost to it.
+#[derive(Debug)]
+pub struct Diagnostic {
+    /// The error, with the offset and key path of the broken region.
+    pub error: Error,
+    /// Number of bytes from the error to the end of the data, which could
+    /// not be read.
+    pub lost_bytes: u64,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        match self.lost_bytes {
+            0 => Ok(()),
+            1 => write!(f, "; 1 byte was not read"),
+            lost_bytes => write!(f, "; {} bytes were not read", lost_bytes),
+        }
+    }
+}
+
+/// One step of the path of keys leading to a value: a compound key or a list
+/// index.
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Formats a key path as in `Inventory[3].tag.ench[0]`.
+fn format_path(path: &[PathSegment]) -> String {
+    let mut formatted = String::new();
+    for segment in path {
+        match segment {
+            PathSegment::Key(key) => {
+                if !formatted.is_empty() {
+                    formatted.push('.');
+                }
+                formatted.push_str(key);
+            }
+            PathSegment::Index(index) => formatted.push_str(&format!("[{}]", index)),
+        }
+    }
+    formatted
+}
+
+/// Largest number of array or list elements allocated before they are read,
+/// so that a corrupt length fails at the end of the data instead of
+/// exhausting memory.
+const MAX_PREALLOCATION: usize = 4096;
+
+/// Deepest nesting of compounds and lists read, so that corrupt or hostile
+/// data cannot overflow the stack.
+pub const MAX_DEPTH: usize = 512;
+
+/// Reads NBT while keeping track of the byte offset and of the path of keys
+/// being read, to report where invalid data was found. Every error it returns
+/// carries that position.
+struct NbtReader<'r, R> {
+    reader: &'r mut R,
+    offset: u64,
+    path: Vec<PathSegment>,
+    mode: ParseMode,
+    /// The error lenient parsing stopped at.
+    stopped: Option<Error>,
+}
+
+impl<'r, R: Read> NbtReader<'r, R> {
+    fn new(reader: &'r mut R, offset: u64, mode: ParseMode) -> Self {
+        NbtReader {
+            reader,
+            offset,
+            path: Vec::new(),
+            mode,
+            stopped: None,
+        }
+    }
+
+    /// Fails in strict mode. In lenient mode, stops reading and returns the
+    /// partly read compound or list, with the path back at its `depth`.
+    fn salvage(&mut self, err: Error, depth: usize, partial: Choice) -> Result<Choice> {
+        if self.mode == ParseMode::Strict {
+            return Err(err);
+        }
+        self.path.truncate(depth);
+        self.stopped = Some(err);
+        Ok(partial)
+    }
+
+    /// Attaches the current key path and the given offset to an error.
+    fn error_at(&self, offset: u64, err: Error) -> Error {
+        Error::Nbt {
+            offset,
+            path: format_path(&self.path),
+            source: Box::new(err),
+        }
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.reader.read_exact(buf).map_err(|err| self.error_at(self.offset, err.into()))?;
+        self.offset += buf.len() as u64;
+        Ok(())
+    }
+
+    fn read_bytes<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut buf = [0; N];
+        self.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn read_tag_type(&mut self) -> Result<TagType> {
+        let offset = self.offset;
+        let [id] = self.read_bytes()?;
+        TagType::from_id(id).ok_or_else(|| Error::InvalidTagType {
+            id,
+            offset,
+            path: format_path(&self.path),
+        })
+    }
+
+    fn read_string(&mut self) -> Result<String> {
+        let offset = self.offset;
+        let length = u16::from_le_bytes(self.read_bytes()?) as usize;
+        let mut string_buf = vec![0; length];
+        self.read_exact(&mut string_buf)?;
+        String::from_utf8(string_buf).map_err(|err| self.error_at(offset, err.into()))
+    }
+
+    fn read_array_length(&mut self) -> Result<usize> {
+        let offset = self.offset;
+        let length = i32::from_le_bytes(self.read_bytes()?);
+        if length < 0 {
+            return Err(self.error_at(offset, Error::invalid_data(format!("Invalid array length: {}", length))));
+        }
+        Ok(length as usize)
+    }
+
+    fn read_byte_array(&mut self) -> Result<Choice> {
+        let length = self.read_array_length()?;
+        let mut byte_array_buf = Vec::with_capacity(length.min(MAX_PREALLOCATION));
+        let read = Read::take(&mut *self.reader, length as u64).read_to_end(&mut byte_array_buf);
+        let read = read.map_err(|err| self.error_at(self.offset, err.into()))?;
+        self.offset += read as u64;
+        if read < length {
+            return Err(self.error_at(self.offset, Error::UnexpectedEof));
+        }
+        Ok(Choice::ByteArray(byte_array_buf.into_iter().map(|byte| byte as i8).collect()))
+    }
+
+    fn read_list(&mut self) -> Result<Choice> {
+        let element_type = self.read_tag_type()?;
+        let length = self.read_array_length()?;
+        let mut values = Vec::with_capacity(length.min(MAX_PREALLOCATION));
+        for index in 0..length {
+            let depth = self.path.len();
+            self.path.push(PathSegment::Index(index));
+            let element = match self.read_choice(element_type.clone()) {
+                Ok(element) => element,
+                Err(err) => return self.salvage(err, depth, Choice::List(element_type, values)),
+            };
+            self.path.pop();
+            values.push(element);
+            if self.stopped.is_some() {
+                break;
+            }
+        }
+        Ok(Choice::List(element_type, values))
+    }
+
+    fn read_compound(&mut self) -> Result<Choice> {
+        let mut compound_tags = Vec::new();
+        loop {
+            let depth = self.path.len();
+            let child_tag = match self.read_tag() {
+                Ok(child_tag) => child_tag,
+                Err(err) => return self.salvage(err, depth, Choice::Vec(compound_tags)),
+            };
+            if child_tag.tag_type == TagType::End {
+                break;
+            }
+            compound_tags.push(child_tag);
+            if self.stopped.is_some() {
+                break;
+            }
+        }
+        Ok(Choice::Vec(compound_tags))
+    }
+
+    fn read_int_array(&mut self) -> Result<Choice> {
+        let length = self.read_array_length()?;
+        let mut values = Vec::with_capacity(length.min(MAX_PREALLOCATION));
+        for _ in 0..length {
+            values.push(i32::from_le_bytes(self.read_bytes()?));
+        }
+        Ok(Choice::IntArray(values))
+    }
+
+    fn read_long_array(&mut self) -> Result<Choice> {
+        let length = self.read_array_length()?;
+        let mut values = Vec::with_capacity(length.min(MAX_PREALLOCATION));
+        for _ in 0..length {
+            values.push(i64::from_le_bytes(self.read_bytes()?));
+        }
+        Ok(Choice::LongArray(values))
+    }
+
+    /// Reads one value. Compounds and lists recurse through here, so their
+    /// readers are kept out of line to keep each level's stack frame small.
+    fn read_choice(&mut self, tag_type: TagType) -> Result<Choice> {
+        match tag_type {
+            TagType::End => Err(self.error_at(self.offset, Error::invalid_data("Cannot parse value of End tag"))),
+            TagType::Byte => Ok(Choice::Byte(i8::from_le_bytes(self.read_bytes()?))),
+            TagType::Int16 => Ok(Choice::Int16(i16::from_le_bytes(self.read_bytes()?))),
+            TagType::Int32 => Ok(Choice::Int32(i32::from_le_bytes(self.read_bytes()?))),
+            TagType::Int64 => Ok(Choice::Int64(i64::from_le_bytes(self.read_bytes()?))),
+            TagType::Float => Ok(Choice::Float32(f32::from_le_bytes(self.read_bytes()?))),
+            TagType::Double => Ok(Choice::Float64(f64::from_le_bytes(self.read_bytes()?))),
+            TagType::ByteArray => self.read_byte_array(),
+            TagType::String => Ok(Choice::String(self.read_string()?)),
+            TagType::List | TagType::Compound if self.path.len() > MAX_DEPTH => {
+                // Each nested compound or list adds one segment to the path
+                let message = format!("NBT nested deeper than {} levels", MAX_DEPTH);
+                Err(self.error_at(self.offset, Error::invalid_data(message)))
+            }
+            TagType::List => self.read_list(),
+            TagType::Compound => self.read_compound(),
+            TagType::IntArray => self.read_int_array(),
+            TagType::LongArray => self.read_long_array(),
+        }
+    }
+
+    fn read_tag(&mut self) -> Result<Tag> {
+        let tag_type = self.read_tag_type()?;
+
+        if tag_type == TagType::End {
+            return Ok(Tag {
+                tag_type,
+                key: "".to_string(),
+                choice_value: None,
+            });
+        }
+
+        let key = self.read_string()?;
+        self.path.push(PathSegment::Key(key));
+        let choice_value = self.read_choice(tag_type.clone())?;
+        let Some(PathSegment::Key(key)) = self.path.pop() else { unreachable!() };
+        Ok(Tag {
+            tag_type,
+            key,
+            choice_value: Some(choice_value),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tag {
+    pub tag_type: TagType,
+    pub key: String,
+    pub choice_value: Option<Choice>,
+}
+
+impl Tag {
+    pub fn new<K: Into<String>>(key: K, choice_value: Choice) -> Self {
+        Tag {
+            tag_type: choice_value.tag_type(),
+            key: key.into(),
+            choice_value: Some(choice_value),
+        }
+    }
+
+    /// Returns the child of this compound with the given key.
+    pub fn get(&self, key: &str) -> Option<&Tag> {
+        self.choice_value.as_ref()?
+            .as_compound()?
+            .iter()
+            .find(|child_tag| child_tag.key == key)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Tag> {
+        self.choice_value.as_mut()?
+            .as_compound_mut()?
+            .iter_mut()
+            .find(|child_tag| child_tag.key == key)
+    }
+
+    /// Inserts a child into this compound, replacing and returning the child
+    /// with the same key if there was one.
+    pub fn insert(&mut self, tag: Tag) -> Result<Option<Tag>> {
+        let compound_tags = self.choice_value.as_mut()
+            .and_then(Choice::as_compound_mut)
+            .ok_or_else(|| Error::invalid_input(format!("Tag {} is not a compound", self.key)))?;
+        match compound_tags.iter_mut().find(|child_tag| child_tag.key == tag.key) {
+            Some(child_tag) => Ok(Some(std::mem::replace(child_tag, tag))),
+            None => {
+                compound_tags.push(tag);
+                Ok(None)
+            }
+        }
+    }
+
+    pub fn typed_parse<R: Read>(reader: &mut R, key: String, tag_type: TagType) -> Result<Self> {
+        Ok(Tag {
+            tag_type: tag_type.clone(),
+            key,
+            choice_value: Some(Choice::parse(reader, tag_type)?),
+        })
+    }
+
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self> {
+        Self::parse_at(reader, 0)
+    }
+
+    /// Parses a tag from a reader positioned `offset` bytes into its file or
+    /// record, so that errors report offsets from the start of it.
+    pub fn parse_at<R: Read>(reader: &mut R, offset: u64) -> Result<Self> {
+        NbtReader::new(reader, offset, ParseMode::Strict).read_tag()
+    }
+
+    /// Parses a buffer of concatenated root tags, such as the block entity
+    /// and entity records of a chunk.
+    pub fn parse_all(bytes: &[u8]) -> Result<Vec<Self>> {
+        Ok(Self::parse_all_with(bytes, 0, ParseMode::Strict)?.0)
+    }
+
+    /// Parses concatenated root tags from a buffer that starts `offset` bytes
+    /// into its file or record. In lenient mode a parse error ends parsing
+    /// without failing: the tags read so far are returned along with a
+    /// diagnostic of what was lost.
+    pub fn parse_all_with(mut bytes: &[u8], offset: u64, mode: ParseMode) -> Result<(Vec<Self>, Option<Diagnostic>)> {
+        let end = offset + bytes.len() as u64;
+        let mut reader = NbtReader::new(&mut bytes, offset, mode);
+        let mut tags = Vec::new();
+        while reader.offset < end && reader.stopped.is_none() {
+            match reader.read_tag() {
+                Ok(tag) => tags.push(tag),
+                Err(err) if mode == ParseMode::Lenient => reader.stopped = Some(err),
+                Err(err) => return Err(err),
+            }
+        }
+
+        let diagnostic = reader.stopped.map(|error| {
+            let lost_bytes = end.saturating_sub(error.offset().unwrap_or(offset));
+            Diagnostic { error, lost_bytes }
+        });
+        Ok((tags, diagnostic))
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        if self.tag_type == TagType::End {
+            return self.tag_type.write(writer);
+        }
+
+        let choice_value = self.choice_value.as_ref()
+            .ok_or_else(|| Error::invalid_input(format!("Missing value for tag {}", self.key)))?;
+        if choice_value.tag_type() != self.tag_type {
+            return Err(Error::invalid_input(format!(
+                "Tag {} of type {:?} holds a {:?}", self.key, self.tag_type, choice_value.tag_type(),
+            )));
+        }
+
+        self.tag_type.write(writer)?;
+        write_string(writer, &self.key)?;
+        choice_value.write(writer)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    pub(crate) fn push_name(buf: &mut Vec<u8>, tag_type: u8, name: &str) {
+        buf.push(tag_type);
+        buf.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        buf.extend_from_slice(name.as_bytes());
+    }
+
+    pub(crate) fn sample_level_dat() -> Vec<u8> {
+        let mut body = Vec::new();
+        push_name(&mut body, 10, "");
+
+        push_name(&mut body, 8, "LevelName");
+        body.extend_from_slice(&5u16.to_le_bytes());
+        body.extend_from_slice(b"World");
+        push_name(&mut body, 4, "RandomSeed");
+        body.extend_from_slice(&(-3_141_592_653_589i64).to_le_bytes());
+        push_name(&mut body, 3, "SpawnY");
+        body.extend_from_slice(&32767i32.to_le_bytes());
+        push_name(&mut body, 1, "commandblockoutput");
+        body.push(0xff);
+        push_name(&mut body, 2, "NetherScale");
+        body.extend_from_slice(&8i16.to_le_bytes());
+        push_name(&mut body, 6, "Temperature");
+        body.extend_from_slice(&0.8f64.to_le_bytes());
+        push_name(&mut body, 7, "Bytes");
+        body.extend_from_slice(&3i32.to_le_bytes());
+        body.extend_from_slice(&[0x80, 0x00, 0x7f]);
+        push_name(&mut body, 11, "Ints");
+        body.extend_from_slice(&2i32.to_le_bytes());
+        body.extend_from_slice(&i32::MIN.to_le_bytes());
+        body.extend_from_slice(&i32::MAX.to_le_bytes());
+        push_name(&mut body, 12, "Longs");
+        body.extend_from_slice(&1i32.to_le_bytes());
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+
+        push_name(&mut body, 9, "lastOpenedWithVersion");
+        body.push(3);
+        body.extend_from_slice(&5i32.to_le_bytes());
+        for part in [1i32, 20, 81, 1, 0] {
+            body.extend_from_slice(&part.to_le_bytes());
+        }
+        push_name(&mut body, 9, "EmptyList");
+        body.push(0);
+        body.extend_from_slice(&0i32.to_le_bytes());
+
+        push_name(&mut body, 9, "Compounds");
+        body.push(10);
+        body.extend_from_slice(&2i32.to_le_bytes());
+        push_name(&mut body, 8, "id");
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.push(0);
+        body.push(0);
+
+        push_name(&mut body, 10, "abilities");
+        push_name(&mut body, 5, "flySpeed");
+        body.extend_from_slice(&0.05f32.to_le_bytes());
+        push_name(&mut body, 1, "mayfly");
+        body.push(0);
+        body.push(0);
+
+        body.push(0);
+
+        let mut level_dat = Vec::new();
+        level_dat.extend_from_slice(&10i32.to_le_bytes());
+        level_dat.extend_from_slice(&(body.len() as i32).to_le_bytes());
+        level_dat.extend_from_slice(&body);
+        level_dat
+    }
+
+    /// A `level.dat` with the keys and tag types Bedrock 1.20.81 saves.
+    pub(crate) const LEVEL_DAT_FIXTURE: &[u8] = include_bytes!("../../tests/data/level.dat");
+
+    #[test]
+    fn level_dat_round_trip() {
+        for level_dat in [sample_level_dat(), LEVEL_DAT_FIXTURE.to_vec()] {
+            let mut reader = Cursor::new(&level_dat[8..]);
+
+            let mut tags = Vec::new();
+            while let Ok(tag) = Tag::parse(&mut reader) {
+                if tag.tag_type == TagType::End {
+                    break;
+                }
+                tags.push(tag);
+            }
+            assert_eq!(tags.len(), 1);
+            assert_eq!(reader.position() as usize, level_dat.len() - 8);
+
+            let mut written = level_dat[..8].to_vec();
+            for tag in &tags {
+                tag.write(&mut written).unwrap();
+            }
+            assert_eq!(written, level_dat);
+
+            let reparsed = Tag::parse(&mut Cursor::new(&written[8..])).unwrap();
+            assert_eq!(reparsed, tags[0]);
+        }
+    }
+
+    #[test]
+    fn invalid_tag_type_path() {
+        let mut body = Vec::new();
+        push_name(&mut body, 10, "");
+        push_name(&mut body, 9, "Inventory");
+        body.push(10);
+        body.extend_from_slice(&2i32.to_le_bytes());
+        body.push(0);
+        push_name(&mut body, 10, "tag");
+        body.push(13);
+
+        match Tag::parse(&mut Cursor::new(&body)) {
+            Err(Error::InvalidTagType { id, offset, path }) => {
+                assert_eq!((id, offset, path.as_str()), (13, body.len() as u64 - 1, "Inventory[1].tag"));
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn error_position() {
+        let level_dat = sample_level_dat();
+        let fly_speed = level_dat.windows(8).position(|window| window == b"flySpeed").unwrap() + 8;
+        let truncated = &level_dat[8..fly_speed + 2];
+
+        let err = Tag::parse_at(&mut Cursor::new(truncated), 8).unwrap_err();
+        match &err {
+            Error::Nbt { offset, path, source } => {
+                assert_eq!((*offset, path.as_str()), (fly_speed as u64, "abilities.flySpeed"));
+                assert!(matches!(**source, Error::UnexpectedEof));
+            }
+            _ => panic!("Unexpected error: {:?}", err),
+        }
+        assert!(matches!(err.cause(), Error::UnexpectedEof));
+        assert_eq!(err.to_string(), format!("Unexpected end of data at offset {} in abilities.flySpeed", fly_speed));
+    }
+
+    #[test]
+    fn lenient_salvage() {
+        let level_dat = sample_level_dat();
+        let fly_speed = level_dat.windows(8).position(|window| window == b"flySpeed").unwrap() + 8;
+        let truncated = &level_dat[8..fly_speed + 2];
+
+        assert!(Tag::parse_all_with(truncated, 8, ParseMode::Strict).is_err());
+
+        let (tags, diagnostic) = Tag::parse_all_with(truncated, 8, ParseMode::Lenient).unwrap();
+        let diagnostic = diagnostic.unwrap();
+        assert_eq!(diagnostic.error.offset(), Some(fly_speed as u64));
+        assert_eq!(diagnostic.lost_bytes, 2);
+
+        // Everything before flySpeed is kept, including the abilities compound it cut short
+        let original = Tag::parse_all(&level_dat[8..]).unwrap();
+        let root = tags[0].choice_value.as_ref().and_then(Choice::as_compound).unwrap();
+        let original_root = original[0].choice_value.as_ref().and_then(Choice::as_compound).unwrap();
+        assert_eq!(root.len(), original_root.len());
+        assert_eq!(root[..root.len() - 1], original_root[..original_root.len() - 1]);
+        assert_eq!(tags[0].get("abilities").unwrap().choice_value, Some(Choice::Vec(Vec::new())));
+    }
+
+    #[test]
+    fn corrupt_lengths() {
+        // A list of Int claiming -1 elements
+        let mut body = Vec::new();
+        push_name(&mut body, 10, "");
+        push_name(&mut body, 9, "List");
+        body.push(3);
+        body.extend_from_slice(&(-1i32).to_le_bytes());
+        body.push(0);
+        let err = Tag::parse(&mut Cursor::new(&body)).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid array length: -1 at offset 11 in List");
+
+        // Arrays claiming far more elements than the data holds
+        for tag_type in [7, 11, 12] {
+            let mut body = Vec::new();
+            push_name(&mut body, tag_type, "Array");
+            body.extend_from_slice(&i32::MAX.to_le_bytes());
+            body.extend_from_slice(&[0; 16]);
+            let err = Tag::parse(&mut Cursor::new(&body)).unwrap_err();
+            assert!(matches!(err.cause(), Error::UnexpectedEof), "{:?}", err);
+        }
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let levels = 100_000;
+        let mut body = Vec::new();
+        push_name(&mut body, 9, "");
+        for _ in 0..levels {
+            body.push(9);
+            body.extend_from_slice(&1i32.to_le_bytes());
+        }
+        body.push(3);
+        body.extend_from_slice(&0i32.to_le_bytes());
+
+        let err = Tag::parse_all_with(&body, 0, ParseMode::Strict).unwrap_err();
+        assert_eq!(err.cause().to_string(), format!("NBT nested deeper than {} levels", MAX_DEPTH));
+
+        let (tags, diagnostic) = Tag::parse_all_with(&body, 0, ParseMode::Lenient).unwrap();
+        assert_eq!(diagnostic.unwrap().error.offset(), err.offset());
+        let mut depth = 0;
+        let mut choice_value = tags[0].choice_value.as_ref().unwrap();
+        while let Choice::List(_, values) = choice_value {
+            let Some(value) = values.first() else { break };
+            choice_value = value;
+            depth += 1;
+        }
+        assert_eq!(depth, MAX_DEPTH - 1);
+    }
+
+    #[test]
+    fn write_mismatched_types() {
+        let list = Tag::new("List", Choice::List(TagType::Int32, vec![Choice::Int32(1), Choice::Byte(1)]));
+        let err = list.write(&mut Vec::new()).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput(_)));
+        assert_eq!(err.to_string(), "List of Int32 holds a Byte");
+
+        let mut tag = Tag::new("Count", Choice::Byte(1));
+        tag.tag_type = TagType::Int32;
+        let mut written = Vec::new();
+        let err = tag.write(&mut written).unwrap_err();
+        assert_eq!(err.to_string(), "Tag Count of type Int32 holds a Byte");
+        assert!(written.is_empty());
+
+        let nested = Tag::new("", Choice::Vec(vec![list]));
+        assert!(nested.write(&mut Vec::new()).is_err());
+    }
+}
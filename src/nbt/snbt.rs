@@ -0,0 +1,585 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write;
+
+use crate::nbt::{Choice, Tag, TagType, MAX_DEPTH};
+
+const INDENT: &str = "    ";
+
+/// Layout of stringified NBT: everything on one line without spaces, or one
+/// compound entry per line with indentation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnbtFormat {
+    Compact,
+    Pretty,
+}
+
+impl Choice {
+    pub fn to_snbt(&self, format: SnbtFormat) -> String {
+        let mut snbt = String::new();
+        write_choice(&mut snbt, self, format, 0).unwrap();
+        snbt
+    }
+}
+
+impl Tag {
+    /// Formats the tag as `key: value`, or as the value alone for root tags
+    /// without a key.
+    pub fn to_snbt(&self, format: SnbtFormat) -> String {
+        let mut snbt = String::new();
+        if !self.key.is_empty() {
+            write_key(&mut snbt, &self.key).unwrap();
+            snbt.push_str(separator(format, ":"));
+        }
+        if let Some(choice_value) = &self.choice_value {
+            write_choice(&mut snbt, choice_value, format, 0).unwrap();
+        }
+        snbt
+    }
+}
+
+impl Choice {
+    /// Parses stringified NBT. Integers without a suffix are ints and decimals
+    /// without a suffix are doubles, as in commands.
+    pub fn from_snbt(text: &str) -> Result<Self, SnbtError> {
+        let mut parser = Parser { text, position: 0, depth: 0 };
+        let choice_value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.position < text.len() {
+            return Err(parser.error("Unexpected trailing characters"));
+        }
+        Ok(choice_value)
+    }
+}
+
+/// An SNBT syntax error, with the byte offset, line and column (both from 1)
+/// where it was found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnbtError {
+    pub message: String,
+    pub position: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for SnbtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at line {}, column {}", self.message, self.line, self.column)
+    }
+}
+
+impl Error for SnbtError {}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+    /// Number of compounds and lists being parsed.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error_at(&self, position: usize, message: &str) -> SnbtError {
+        let before = &self.text[..position];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rfind('\n').map_or(before, |newline| &before[newline + 1..]).chars().count() + 1;
+        SnbtError {
+            message: message.to_string(),
+            position,
+            line,
+            column,
+        }
+    }
+
+    fn error(&self, message: &str) -> SnbtError {
+        self.error_at(self.position, message)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(character) = self.peek().filter(|character| character.is_whitespace()) {
+            self.position += character.len_utf8();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SnbtError> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected '{}'", expected)))
+        }
+    }
+
+    /// Consumes `expected` if it comes next.
+    fn accept(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Choice, SnbtError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') | Some('[') if self.depth >= MAX_DEPTH => {
+                Err(self.error(&format!("Nested deeper than {} levels", MAX_DEPTH)))
+            }
+            Some(bracket @ ('{' | '[')) => {
+                self.depth += 1;
+                let value = if bracket == '{' { self.parse_compound() } else { self.parse_list() };
+                self.depth -= 1;
+                value
+            }
+            Some('"') | Some('\'') => Ok(Choice::String(self.parse_quoted()?)),
+            Some(_) => self.parse_bare_value(),
+            None => Err(self.error("Expected a value")),
+        }
+    }
+
+    fn parse_quoted(&mut self) -> Result<String, SnbtError> {
+        let start = self.position;
+        let quote = self.peek().ok_or_else(|| self.error("Expected a string"))?;
+        self.position += 1;
+        let mut value = String::new();
+        loop {
+            let character = self.peek().ok_or_else(|| self.error_at(start, "Unterminated string"))?;
+            self.position += character.len_utf8();
+            match character {
+                '\\' => {
+                    let escaped = self.peek().ok_or_else(|| self.error_at(start, "Unterminated string"))?;
+                    value.push(match escaped {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        '\\' | '"' | '\'' => escaped,
+                        _ => return Err(self.error(&format!("Invalid escape '\\{}'", escaped))),
+                    });
+                    self.position += escaped.len_utf8();
+                }
+                _ if character == quote => return Ok(value),
+                _ => value.push(character),
+            }
+        }
+    }
+
+    fn parse_bare(&mut self) -> &'a str {
+        let start = self.position;
+        while let Some(character) = self.peek().filter(|&character| is_bare_char(character)) {
+            self.position += character.len_utf8();
+        }
+        &self.text[start..self.position]
+    }
+
+    fn parse_key(&mut self) -> Result<String, SnbtError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') | Some('\'') => self.parse_quoted(),
+            _ => {
+                let key = self.parse_bare();
+                if key.is_empty() {
+                    return Err(self.error("Expected a key"));
+                }
+                Ok(key.to_string())
+            }
+        }
+    }
+
+    fn parse_bare_value(&mut self) -> Result<Choice, SnbtError> {
+        let start = self.position;
+        let token = self.parse_bare();
+        if token.is_empty() {
+            return Err(self.error("Expected a value"));
+        }
+        parse_scalar(token).map_err(|message| self.error_at(start, &message))
+    }
+
+    fn parse_compound(&mut self) -> Result<Choice, SnbtError> {
+        self.expect('{')?;
+        let mut compound_tags: Vec<Tag> = Vec::new();
+        if self.accept('}') {
+            return Ok(Choice::Vec(compound_tags));
+        }
+        loop {
+            self.skip_whitespace();
+            let key_position = self.position;
+            let key = self.parse_key()?;
+            if compound_tags.iter().any(|child_tag| child_tag.key == key) {
+                return Err(self.error_at(key_position, &format!("Duplicate key '{}'", key)));
+            }
+            self.expect(':')?;
+            compound_tags.push(Tag::new(key, self.parse_value()?));
+            if self.accept('}') {
+                return Ok(Choice::Vec(compound_tags));
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn parse_list(&mut self) -> Result<Choice, SnbtError> {
+        self.expect('[')?;
+
+        // Typed arrays start with their element type
+        let rest = &self.text[self.position..];
+        let array_type = ['B', 'I', 'L'].into_iter().find(|&prefix| {
+            rest.starts_with(prefix) && rest[1..].trim_start().starts_with(';')
+        });
+        if let Some(array_type) = array_type {
+            self.position += 1;
+            self.expect(';')?;
+            return self.parse_array(array_type);
+        }
+
+        let mut values: Vec<Choice> = Vec::new();
+        if self.accept(']') {
+            return Ok(Choice::List(TagType::End, values));
+        }
+        loop {
+            self.skip_whitespace();
+            let element_position = self.position;
+            let value = self.parse_value()?;
+            if let Some(first) = values.first() {
+                if first.tag_type() != value.tag_type() {
+                    return Err(self.error_at(element_position, &format!(
+                        "List element of type {:?} in a list of {:?}", value.tag_type(), first.tag_type(),
+                    )));
+                }
+            }
+            values.push(value);
+            if self.accept(']') {
+                break;
+            }
+            self.expect(',')?;
+        }
+        Ok(Choice::List(values[0].tag_type(), values))
+    }
+
+    fn parse_array(&mut self, array_type: char) -> Result<Choice, SnbtError> {
+        let mut values = Vec::new();
+        if !self.accept(']') {
+            loop {
+                self.skip_whitespace();
+                let start = self.position;
+                let token = self.parse_bare();
+                let digits = token.strip_suffix(['b', 'B', 'l', 'L']).unwrap_or(token);
+                let suffix_matches = token.len() == digits.len()
+                    || token.ends_with(array_type.to_ascii_lowercase())
+                    || token.ends_with(array_type);
+                if array_type == 'I' && token.len() != digits.len() || !suffix_matches {
+                    return Err(self.error_at(start, &format!("Invalid element '{}' in a [{}; ...] array", token, array_type)));
+                }
+                let value = digits.parse::<i64>()
+                    .map_err(|_| self.error_at(start, &format!("Invalid element '{}' in a [{}; ...] array", token, array_type)))?;
+                values.push((start, value));
+                if self.accept(']') {
+                    break;
+                }
+                self.expect(',')?;
+            }
+        }
+
+        let out_of_range = |(start, _): &(usize, i64)| self.error_at(*start, "Array element out of range");
+        match array_type {
+            'B' => values.iter()
+                .map(|element| i8::try_from(element.1).map_err(|_| out_of_range(element)))
+                .collect::<Result<_, _>>()
+                .map(Choice::ByteArray),
+            'I' => values.iter()
+                .map(|element| i32::try_from(element.1).map_err(|_| out_of_range(element)))
+                .collect::<Result<_, _>>()
+                .map(Choice::IntArray),
+            _ => Ok(Choice::LongArray(values.into_iter().map(|(_, value)| value).collect())),
+        }
+    }
+}
+
+fn is_bare_char(character: char) -> bool {
+    character.is_ascii_alphanumeric() || "_-.+".contains(character)
+}
+
+/// Interprets an unquoted token as a number or a boolean, falling back to a
+/// string when it is neither.
+fn parse_scalar(token: &str) -> Result<Choice, String> {
+    match token {
+        "true" => return Ok(Choice::Byte(1)),
+        "false" => return Ok(Choice::Byte(0)),
+        _ => {}
+    }
+
+    let is_integer = |digits: &str| {
+        let digits = digits.strip_prefix(['-', '+']).unwrap_or(digits);
+        !digits.is_empty() && digits.chars().all(|character| character.is_ascii_digit())
+    };
+    let is_decimal = |digits: &str| is_integer(digits) || digits.parse::<f64>().is_ok() && digits.chars().any(|character| character.is_ascii_digit());
+    // Non-finite values as written by `to_snbt`, only with a float suffix
+    let is_float = |digits: &str| is_decimal(digits) || matches!(digits, "NaN" | "inf" | "+inf" | "-inf");
+    let out_of_range = || format!("Number out of range: {}", token);
+
+    let (digits, suffix) = token.split_at(token.len() - 1);
+    if let Some(bits) = digits.strip_prefix("NaN_").filter(|bits| bits.chars().all(|character| character.is_ascii_hexdigit())) {
+        let nan = match (suffix, bits.len()) {
+            ("f" | "F", 8) => u32::from_str_radix(bits, 16).ok().map(f32::from_bits).filter(|value| value.is_nan()).map(Choice::Float32),
+            ("d" | "D", 16) => u64::from_str_radix(bits, 16).ok().map(f64::from_bits).filter(|value| value.is_nan()).map(Choice::Float64),
+            _ => None,
+        };
+        if let Some(nan) = nan {
+            return Ok(nan);
+        }
+    }
+    match suffix {
+        "b" | "B" if is_integer(digits) => return digits.parse().map(Choice::Byte).map_err(|_| out_of_range()),
+        "s" | "S" if is_integer(digits) => return digits.parse().map(Choice::Int16).map_err(|_| out_of_range()),
+        "l" | "L" if is_integer(digits) => return digits.parse().map(Choice::Int64).map_err(|_| out_of_range()),
+        "f" | "F" if is_float(digits) => return digits.parse().map(Choice::Float32).map_err(|_| out_of_range()),
+        "d" | "D" if is_float(digits) => return digits.parse().map(Choice::Float64).map_err(|_| out_of_range()),
+        _ => {}
+    }
+    if is_integer(token) {
+        return token.parse().map(Choice::Int32).map_err(|_| out_of_range());
+    }
+    if is_decimal(token) && token.contains(['.', 'e', 'E']) {
+        return token.parse().map(Choice::Float64).map_err(|_| out_of_range());
+    }
+    Ok(Choice::String(token.to_string()))
+}
+
+/// Formats compact SNBT, or pretty SNBT with the alternate flag (`{:#}`).
+impl fmt::Display for Choice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let format = if f.alternate() { SnbtFormat::Pretty } else { SnbtFormat::Compact };
+        write_choice(f, self, format, 0)
+    }
+}
+
+fn separator(format: SnbtFormat, separator: &'static str) -> &'static str {
+    match (format, separator) {
+        (SnbtFormat::Compact, _) => separator,
+        (SnbtFormat::Pretty, ":") => ": ",
+        (SnbtFormat::Pretty, ",") => ", ",
+        (SnbtFormat::Pretty, ";") => "; ",
+        (SnbtFormat::Pretty, _) => separator,
+    }
+}
+
+fn write_indent<W: Write>(out: &mut W, depth: usize) -> fmt::Result {
+    for _ in 0..depth {
+        out.write_str(INDENT)?;
+    }
+    Ok(())
+}
+
+/// Returns whether a string can be written without quotes.
+pub(crate) fn is_bare(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(is_bare_char)
+}
+
+pub(crate) fn write_quoted<W: Write>(out: &mut W, text: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for character in text.chars() {
+        match character {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\t' => out.write_str("\\t")?,
+            '\r' => out.write_str("\\r")?,
+            _ => out.write_char(character)?,
+        }
+    }
+    out.write_char('"')
+}
+
+fn write_key<W: Write>(out: &mut W, key: &str) -> fmt::Result {
+    if is_bare(key) {
+        out.write_str(key)
+    } else {
+        write_quoted(out, key)
+    }
+}
+
+fn write_array<W: Write, T: fmt::Display>(out: &mut W, prefix: &str, values: &[T], suffix: &str, format: SnbtFormat) -> fmt::Result {
+    write!(out, "[{}{}", prefix, separator(format, ";"))?;
+    for (position, value) in values.iter().enumerate() {
+        if position > 0 {
+            out.write_str(separator(format, ","))?;
+        }
+        write!(out, "{}{}", value, suffix)?;
+    }
+    out.write_char(']')
+}
+
+fn write_choice<W: Write>(out: &mut W, choice_value: &Choice, format: SnbtFormat, depth: usize) -> fmt::Result {
+    match choice_value {
+        Choice::Byte(value) => write!(out, "{}b", value),
+        Choice::Int16(value) => write!(out, "{}s", value),
+        Choice::Int32(value) => write!(out, "{}", value),
+        Choice::Int64(value) => write!(out, "{}L", value),
+        // `NaN` only reads back as one of the NaNs, so the others keep their bits
+        Choice::Float32(value) if value.is_nan() && value.to_bits() != f32::NAN.to_bits() => write!(out, "NaN_{:08x}f", value.to_bits()),
+        Choice::Float64(value) if value.is_nan() && value.to_bits() != f64::NAN.to_bits() => write!(out, "NaN_{:016x}d", value.to_bits()),
+        Choice::Float32(value) => write!(out, "{:?}f", value),
+        Choice::Float64(value) => write!(out, "{:?}d", value),
+        Choice::ByteArray(values) => write_array(out, "B", values, "b", format),
+        Choice::IntArray(values) => write_array(out, "I", values, "", format),
+        Choice::LongArray(values) => write_array(out, "L", values, "L", format),
+        Choice::String(value) => write_quoted(out, value),
+        Choice::List(_, values) => {
+            if values.is_empty() {
+                return out.write_str("[]");
+            }
+            // Only lists of compounds and lists get one element per line
+            let multiline = format == SnbtFormat::Pretty
+                && matches!(values[0], Choice::Vec(_) | Choice::List(_, _));
+            out.write_char('[')?;
+            for (position, value) in values.iter().enumerate() {
+                if position > 0 {
+                    out.write_str(if multiline { "," } else { separator(format, ",") })?;
+                }
+                if multiline {
+                    out.write_char('\n')?;
+                    write_indent(out, depth + 1)?;
+                }
+                write_choice(out, value, format, depth + 1)?;
+            }
+            if multiline {
+                out.write_char('\n')?;
+                write_indent(out, depth)?;
+            }
+            out.write_char(']')
+        }
+        Choice::Vec(compound_tags) => {
+            if compound_tags.is_empty() {
+                return out.write_str("{}");
+            }
+            out.write_char('{')?;
+            for (position, child_tag) in compound_tags.iter().enumerate() {
+                if position > 0 {
+                    out.write_char(',')?;
+                }
+                if format == SnbtFormat::Pretty {
+                    out.write_char('\n')?;
+                    write_indent(out, depth + 1)?;
+                }
+                write_key(out, &child_tag.key)?;
+                out.write_str(separator(format, ":"))?;
+                if let Some(child_value) = &child_tag.choice_value {
+                    write_choice(out, child_value, format, depth + 1)?;
+                }
+            }
+            if format == SnbtFormat::Pretty {
+                out.write_char('\n')?;
+                write_indent(out, depth)?;
+            }
+            out.write_char('}')
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::TagType;
+
+    #[test]
+    fn compact_and_pretty() {
+        let compound = Choice::Vec(vec![
+            Tag::new("LevelName", Choice::String("My \"World\"".to_string())),
+            Tag::new("flat world", Choice::Byte(1)),
+            Tag::new("scale", Choice::Int16(8)),
+            Tag::new("seed", Choice::Int64(-5)),
+            Tag::new("speed", Choice::Float32(0.5)),
+            Tag::new("temperature", Choice::Float64(1.0)),
+            Tag::new("bytes", Choice::ByteArray(vec![1, -1])),
+            Tag::new("version", Choice::List(TagType::Int32, vec![Choice::Int32(1), Choice::Int32(20)])),
+            Tag::new("items", Choice::List(TagType::Compound, vec![Choice::Vec(vec![
+                Tag::new("Count", Choice::Byte(64)),
+            ])])),
+            Tag::new("empty", Choice::Vec(Vec::new())),
+        ]);
+
+        assert_eq!(
+            compound.to_snbt(SnbtFormat::Compact),
+            "{LevelName:\"My \\\"World\\\"\",\"flat world\":1b,scale:8s,seed:-5L,speed:0.5f,temperature:1.0d,\
+             bytes:[B;1b,-1b],version:[1,20],items:[{Count:64b}],empty:{}}"
+        );
+        assert_eq!(compound.to_snbt(SnbtFormat::Pretty), "\
+{
+    LevelName: \"My \\\"World\\\"\",
+    \"flat world\": 1b,
+    scale: 8s,
+    seed: -5L,
+    speed: 0.5f,
+    temperature: 1.0d,
+    bytes: [B; 1b, -1b],
+    version: [1, 20],
+    items: [
+        {
+            Count: 64b
+        }
+    ],
+    empty: {}
+}");
+    }
+
+    #[test]
+    fn parse_round_trip() {
+        let text = r#"{LevelName:"My \"World\"","flat world":1b,scale:8s,seed:-5L,speed:0.5f,temperature:1.0d,bytes:[B;1b,-1b],longs:[L;3L],version:[1,20],items:[{Count:64b}],empty:{},none:[],name:"stone"}"#;
+        let choice_value = Choice::from_snbt(text).unwrap();
+        assert_eq!(choice_value.to_snbt(SnbtFormat::Compact), text);
+        assert_eq!(Choice::from_snbt(&choice_value.to_snbt(SnbtFormat::Pretty)).unwrap(), choice_value);
+
+        let text = "[NaNf,inff,-inff,1.5f]";
+        let choice_value = Choice::from_snbt(text).unwrap();
+        assert_eq!(choice_value.to_snbt(SnbtFormat::Compact), text);
+        let Choice::List(TagType::Float, floats) = &choice_value else { panic!("{:?}", choice_value) };
+        assert!(matches!(floats[0], Choice::Float32(value) if value.is_nan()));
+        assert_eq!(floats[1..3], [Choice::Float32(f32::INFINITY), Choice::Float32(f32::NEG_INFINITY)]);
+        let text = "[NaNd,infd,-infd]";
+        assert_eq!(Choice::from_snbt(text).unwrap().to_snbt(SnbtFormat::Compact), text);
+        assert_eq!(Choice::from_snbt("NaN").unwrap(), Choice::String("NaN".to_string()));
+
+        // NaNs other than the canonical one keep their bits
+        let text = "[NaN_7fc00001f,NaN_ffc00000f,NaN_7f80000ff]";
+        let Choice::List(TagType::Float, floats) = Choice::from_snbt(text).unwrap() else { panic!("{}", text) };
+        let bits: Vec<u32> = floats.iter().map(|value| value.as_f32().unwrap().to_bits()).collect();
+        assert_eq!(bits, [0x7fc0_0001, 0xffc0_0000, 0x7f80_000f]);
+        assert_eq!(Choice::List(TagType::Float, floats).to_snbt(SnbtFormat::Compact), text);
+        let nan = Choice::Float64(f64::from_bits(0x7ff8_0000_0000_0001));
+        assert_eq!(nan.to_snbt(SnbtFormat::Compact), "NaN_7ff8000000000001d");
+        let Choice::Float64(value) = Choice::from_snbt("NaN_7ff8000000000001d").unwrap() else { panic!() };
+        assert_eq!(value.to_bits(), 0x7ff8_0000_0000_0001);
+        for text in ["NaN_3f800000f", "NaN_7fc0001f", "NaN_+fc00001f", "NaN_7fc00001d"] {
+            assert_eq!(Choice::from_snbt(text).unwrap(), Choice::String(text.to_string()));
+        }
+
+        assert_eq!(Choice::from_snbt("{'a b': true, c: 1.5}").unwrap(), Choice::Vec(vec![
+            Tag::new("a b", Choice::Byte(1)),
+            Tag::new("c", Choice::Float64(1.5)),
+        ]));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = Choice::from_snbt("{\n  a: 1,\n  b: [1, 2b]\n}").unwrap_err();
+        assert_eq!(error.message, "List element of type Byte in a list of Int32");
+        assert_eq!((error.position, error.line, error.column), (19, 3, 10));
+
+        let error = Choice::from_snbt("{a: 300b}").unwrap_err();
+        assert_eq!(error.message, "Number out of range: 300b");
+        assert_eq!(error.column, 5);
+
+        let error = Choice::from_snbt("{a: 1} x").unwrap_err();
+        assert_eq!(error.to_string(), "Unexpected trailing characters at line 1, column 8");
+
+        let error = Choice::from_snbt(&"[".repeat(100_000)).unwrap_err();
+        assert_eq!(error.message, format!("Nested deeper than {} levels", MAX_DEPTH));
+        assert_eq!(error.position, MAX_DEPTH);
+        let nested = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert!(Choice::from_snbt(&nested).is_ok());
+    }
+}
@@ -0,0 +1,158 @@
+use std::io::Cursor;
+
+use crate::error::{Error, Result};
+use crate::nbt::{Choice, Tag};
+
+/// A player, read from the `~local_player` record or from a remote player
+/// record.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Player {
+    pub position: Option<[f32; 3]>,
+    /// Yaw and pitch, in degrees.
+    pub rotation: Option<[f32; 2]>,
+    pub dimension: Option<i32>,
+    pub xp_level: Option<i32>,
+    pub xp_progress: Option<f32>,
+    pub spawn_point: Option<[i32; 3]>,
+    pub spawn_dimension: Option<i32>,
+    pub tag: Tag,
+}
+
+impl Player {
+    pub fn from_tag(tag: Tag) -> Result<Self> {
+        if tag.choice_value.as_ref().and_then(Choice::as_compound).is_none() {
+            return Err(Error::invalid_data("Player record is not a compound"));
+        }
+        let value = |key: &str| tag.get(key).and_then(|child_tag| child_tag.choice_value.as_ref());
+
+        let spawn_point = match (value("SpawnX"), value("SpawnY"), value("SpawnZ")) {
+            (Some(Choice::Int32(x)), Some(Choice::Int32(y)), Some(Choice::Int32(z))) => Some([*x, *y, *z]),
+            _ => None,
+        };
+
+        Ok(Player {
+            position: value("Pos").and_then(Choice::as_floats),
+            rotation: value("Rotation").and_then(Choice::as_floats),
+            dimension: value("DimensionId").and_then(Choice::as_i32),
+            xp_level: value("PlayerLevel").and_then(Choice::as_i32),
+            xp_progress: value("PlayerLevelProgress").and_then(Choice::as_f32),
+            spawn_point,
+            spawn_dimension: value("SpawnDimension").and_then(Choice::as_i32),
+            tag,
+        })
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        Self::from_tag(Tag::parse(&mut Cursor::new(bytes))?)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.tag.write(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Returns the key of the record holding the player data when this record
+    /// only links to it, as `player_<id>` records do in recent versions.
+    pub fn server_id(&self) -> Option<&str> {
+        self.value("ServerId")?.as_str()
+    }
+
+    fn value(&self, key: &str) -> Option<&Choice> {
+        self.tag.get(key)?.choice_value.as_ref()
+    }
+
+    /// Returns the item compounds of the main inventory and hotbar.
+    pub fn inventory(&self) -> Option<&[Choice]> {
+        self.value("Inventory")?.as_list()
+    }
+
+    pub fn armor(&self) -> Option<&[Choice]> {
+        self.value("Armor")?.as_list()
+    }
+
+    pub fn ender_chest(&self) -> Option<&[Choice]> {
+        self.value("EnderChestInventory")?.as_list()
+    }
+
+    /// Returns a boolean flag from the `abilities` compound, such as `mayfly`
+    /// or `instabuild`.
+    pub fn ability(&self, name: &str) -> Option<bool> {
+        Some(self.tag.get("abilities")?.get(name)?.choice_value.as_ref()?.as_byte()? != 0)
+    }
+
+    pub fn fly_speed(&self) -> Option<f32> {
+        self.tag.get("abilities")?.get("flySpeed")?.choice_value.as_ref()?.as_f32()
+    }
+
+    pub fn walk_speed(&self) -> Option<f32> {
+        self.tag.get("abilities")?.get("walkSpeed")?.choice_value.as_ref()?.as_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tests::push_name;
+
+    fn push_int(buf: &mut Vec<u8>, name: &str, value: i32) {
+        push_name(buf, 3, name);
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn parse() {
+        let mut record = Vec::new();
+        push_name(&mut record, 10, "");
+        push_name(&mut record, 9, "Pos");
+        record.push(5);
+        record.extend_from_slice(&3i32.to_le_bytes());
+        for coordinate in [0.5f32, 71.62, -12.5] {
+            record.extend_from_slice(&coordinate.to_le_bytes());
+        }
+        push_int(&mut record, "DimensionId", 1);
+        push_int(&mut record, "PlayerLevel", 30);
+        push_name(&mut record, 5, "PlayerLevelProgress");
+        record.extend_from_slice(&0.25f32.to_le_bytes());
+        push_int(&mut record, "SpawnX", 10);
+        push_int(&mut record, "SpawnY", -20);
+        push_int(&mut record, "SpawnZ", 30);
+        push_name(&mut record, 10, "abilities");
+        push_name(&mut record, 1, "mayfly");
+        record.push(1);
+        push_name(&mut record, 5, "walkSpeed");
+        record.extend_from_slice(&0.1f32.to_le_bytes());
+        record.push(0);
+        push_name(&mut record, 9, "Inventory");
+        record.push(10);
+        record.extend_from_slice(&2i32.to_le_bytes());
+        push_name(&mut record, 1, "Slot");
+        record.push(0);
+        record.push(0);
+        push_name(&mut record, 1, "Slot");
+        record.push(1);
+        record.push(0);
+        record.push(0);
+
+        let player = Player::parse(&record).unwrap();
+        assert_eq!(player.position, Some([0.5, 71.62, -12.5]));
+        assert_eq!(player.rotation, None);
+        assert_eq!((player.dimension, player.xp_level, player.xp_progress), (Some(1), Some(30), Some(0.25)));
+        assert_eq!((player.spawn_point, player.spawn_dimension), (Some([10, -20, 30]), None));
+        assert_eq!((player.ability("mayfly"), player.ability("instabuild")), (Some(true), None));
+        assert_eq!((player.walk_speed(), player.fly_speed()), (Some(0.1), None));
+        assert_eq!(player.inventory().map(<[Choice]>::len), Some(2));
+        assert_eq!(player.armor(), None);
+        assert_eq!(player.server_id(), None);
+        assert_eq!(player.to_bytes().unwrap(), record);
+    }
+
+    #[test]
+    fn parse_not_compound() {
+        let mut record = Vec::new();
+        push_name(&mut record, 8, "ServerId");
+        record.extend_from_slice(&4u16.to_le_bytes());
+        record.extend_from_slice(b"1234");
+        assert!(matches!(Player::parse(&record), Err(Error::InvalidData(_))));
+    }
+}
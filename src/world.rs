@@ -0,0 +1,436 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+#[cfg(feature = "leveldb")]
+use std::io::Cursor;
+use std::ops::Range;
+
+#[cfg(feature = "leveldb")]
+use leveldb::batch::{Batch, Writebatch};
+#[cfg(feature = "leveldb")]
+use leveldb::database::Database;
+#[cfg(feature = "leveldb")]
+use leveldb::options::WriteOptions;
+
+#[cfg(feature = "leveldb")]
+use crate::block::BlockState;
+use crate::chunk::subchunk::SUB_CHUNK_SIZE;
+use crate::chunk::SubChunk;
+#[cfg(feature = "leveldb")]
+use crate::chunk::{BlockEntity, ChunkColumnMeta};
+#[cfg(feature = "leveldb")]
+use crate::db;
+use crate::db::{ChunkKey, ChunkRecord, DbKey};
+use crate::error::{Error, Result};
+#[cfg(feature = "leveldb")]
+use crate::entity::Entity;
+#[cfg(feature = "leveldb")]
+use crate::level::LevelData;
+use crate::nbt::Tag;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Dimension {
+    Overworld,
+    Nether,
+    End,
+}
+
+impl Dimension {
+    pub const ALL: [Dimension; 3] = [Dimension::Overworld, Dimension::Nether, Dimension::End];
+
+    pub fn from_id(id: i32) -> Option<Self> {
+        match id {
+            0 => Some(Dimension::Overworld),
+            1 => Some(Dimension::Nether),
+            2 => Some(Dimension::End),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        match self {
+            Dimension::Overworld => 0,
+            Dimension::Nether => 1,
+            Dimension::End => 2,
+        }
+    }
+
+    /// Returns the range of Y coordinates blocks can be placed at.
+    pub fn y_range(&self) -> Range<i32> {
+        match self {
+            Dimension::Overworld => -64..320,
+            Dimension::Nether => 0..128,
+            Dimension::End => 0..256,
+        }
+    }
+}
+
+/// A subchunk by dimension, chunk coordinates and Y index.
+pub type SubChunkPosition = (Dimension, i32, i32, i8);
+
+/// Splits absolute world coordinates into the position of their subchunk and
+/// the coordinates of the block within it.
+pub fn sub_chunk_position(dimension: Dimension, x: i32, y: i32, z: i32) -> Result<(SubChunkPosition, [usize; 3])> {
+    if !dimension.y_range().contains(&y) {
+        return Err(Error::invalid_input(format!("Y coordinate {} out of {:?} range", y, dimension)));
+    }
+    let size = SUB_CHUNK_SIZE as i32;
+    let position = (dimension, x.div_euclid(size), z.div_euclid(size), y.div_euclid(size) as i8);
+    let block = [x.rem_euclid(size) as usize, y.rem_euclid(size) as usize, z.rem_euclid(size) as usize];
+    Ok((position, block))
+}
+
+struct CachedSubChunk {
+    sub_chunk: Option<SubChunk>,
+    dirty: bool,
+}
+
+/// Subchunks read for block access and modified in memory. Reading them is
+/// left to the caller, which passes a `load` function returning the stored
+/// subchunk, if any.
+#[derive(Default)]
+pub struct SubChunkCache {
+    sub_chunks: HashMap<SubChunkPosition, CachedSubChunk>,
+}
+
+impl SubChunkCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a subchunk into the cache on first access.
+    fn cached_sub_chunk<L>(&mut self, position: SubChunkPosition, load: L) -> Result<&mut CachedSubChunk>
+    where
+        L: FnOnce(SubChunkPosition) -> Result<Option<SubChunk>>,
+    {
+        match self.sub_chunks.entry(position) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let sub_chunk = load(position)?;
+                Ok(entry.insert(CachedSubChunk { sub_chunk, dirty: false }))
+            }
+        }
+    }
+
+    /// Returns the block state at a position of a subchunk, or `None` where
+    /// no subchunk is stored, which the game treats as air.
+    pub fn get_block<L>(&mut self, position: SubChunkPosition, [x, y, z]: [usize; 3], load: L) -> Result<Option<&Tag>>
+    where
+        L: FnOnce(SubChunkPosition) -> Result<Option<SubChunk>>,
+    {
+        let cached = self.cached_sub_chunk(position, load)?;
+        Ok(cached.sub_chunk.as_ref().and_then(|sub_chunk| sub_chunk.block(x, y, z)))
+    }
+
+    /// Sets the block state at a position of a subchunk. A missing subchunk
+    /// is created as air, as long as `contains_chunk` reports that its chunk
+    /// exists.
+    pub fn set_block<L, C>(&mut self, position: SubChunkPosition, [x, y, z]: [usize; 3], block_state: Tag, load: L, contains_chunk: C) -> Result<()>
+    where
+        L: FnOnce(SubChunkPosition) -> Result<Option<SubChunk>>,
+        C: FnOnce(i32, i32) -> Result<bool>,
+    {
+        let (_, chunk_x, chunk_z, y_index) = position;
+        let cached = self.cached_sub_chunk(position, load)?;
+        let sub_chunk = match &mut cached.sub_chunk {
+            Some(sub_chunk) => sub_chunk,
+            None => {
+                if !contains_chunk(chunk_x, chunk_z)? {
+                    return Err(Error::invalid_input(format!("Chunk {}, {} is not generated", chunk_x, chunk_z)));
+                }
+                cached.sub_chunk.insert(SubChunk::air(y_index))
+            }
+        };
+        sub_chunk.set_block(x, y, z, block_state)?;
+        cached.dirty = true;
+        Ok(())
+    }
+
+    /// Serializes every modified subchunk into the key and value to write.
+    pub fn changes(&self) -> Result<Vec<(DbKey, Vec<u8>)>> {
+        let mut changes = Vec::new();
+        for (&(dimension, x, z, y_index), cached) in &self.sub_chunks {
+            if let (true, Some(sub_chunk)) = (cached.dirty, &cached.sub_chunk) {
+                let mut value = Vec::new();
+                sub_chunk.write(&mut value)?;
+                changes.push((DbKey::Chunk(ChunkKey { x, z, dimension, record: ChunkRecord::SubChunkPrefix(y_index) }), value));
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Marks every subchunk as unmodified, once `changes` were written.
+    pub fn mark_written(&mut self) {
+        self.sub_chunks.values_mut().for_each(|cached| cached.dirty = false);
+    }
+}
+
+/// A world directory: its `level.dat` metadata and its database, with the
+/// subchunks accessed through `get_block` and `set_block` cached in memory.
+#[cfg(feature = "leveldb")]
+pub struct World {
+    world_dir: String,
+    level_data: Option<LevelData>,
+    database: Database<DbKey>,
+    sub_chunks: SubChunkCache,
+}
+
+#[cfg(feature = "leveldb")]
+impl World {
+    /// Opens the database of a world directory. `level.dat` is only read
+    /// when `level_data` is first called, so a corrupt one does not keep the
+    /// database from being read.
+    pub fn open(world_dir: &str) -> Result<Self> {
+        let database = db::open(world_dir)?;
+        Ok(World {
+            world_dir: world_dir.to_string(),
+            level_data: None,
+            database,
+            sub_chunks: SubChunkCache::new(),
+        })
+    }
+
+    /// Opens the database of a world directory along with level data that
+    /// was already read, for instance with `LevelData::from_file_with`.
+    pub fn with_level_data(world_dir: &str, level_data: LevelData) -> Result<Self> {
+        let mut world = Self::open(world_dir)?;
+        world.level_data = Some(level_data);
+        Ok(world)
+    }
+
+    /// Returns the `level.dat` metadata, reading it on first access.
+    pub fn level_data(&mut self) -> Result<&mut LevelData> {
+        if self.level_data.is_none() {
+            self.level_data = Some(LevelData::from_file(&self.world_dir)?);
+        }
+        Ok(self.level_data.as_mut().unwrap())
+    }
+
+    pub fn database(&self) -> &Database<DbKey> {
+        &self.database
+    }
+
+    pub fn dimension(&self, dimension: Dimension) -> WorldDimension<'_> {
+        WorldDimension {
+            database: &self.database,
+            dimension,
+        }
+    }
+
+    /// Returns the block state at absolute world coordinates, or `None` where
+    /// no subchunk is stored, which the game treats as air.
+    pub fn get_block(&mut self, dimension: Dimension, x: i32, y: i32, z: i32) -> Result<Option<&Tag>> {
+        let (position, block) = sub_chunk_position(dimension, x, y, z)?;
+        let database = &self.database;
+        self.sub_chunks.get_block(position, block, |(dimension, x, z, y_index)| {
+            WorldDimension { database, dimension }.sub_chunk(x, z, y_index)
+        })
+    }
+
+    /// Sets the block state at absolute world coordinates. The change is kept
+    /// in memory until `flush` writes it to the database.
+    pub fn set_block(&mut self, dimension: Dimension, x: i32, y: i32, z: i32, block_state: Tag) -> Result<()> {
+        let (position, block) = sub_chunk_position(dimension, x, y, z)?;
+        let world_dimension = WorldDimension { database: &self.database, dimension };
+        self.sub_chunks.set_block(
+            position,
+            block,
+            block_state,
+            |(_, x, z, y_index)| world_dimension.sub_chunk(x, z, y_index),
+            |x, z| world_dimension.contains_chunk(x, z),
+        )
+    }
+
+    pub fn get_block_state(&mut self, dimension: Dimension, x: i32, y: i32, z: i32) -> Result<Option<BlockState>> {
+        self.get_block(dimension, x, y, z)?
+            .map(BlockState::from_tag)
+            .transpose()
+    }
+
+    pub fn set_block_state(&mut self, dimension: Dimension, x: i32, y: i32, z: i32, block_state: &BlockState) -> Result<()> {
+        self.set_block(dimension, x, y, z, block_state.to_tag())
+    }
+
+    /// Writes every modified subchunk to the database in a single batch.
+    pub fn flush(&mut self) -> Result<()> {
+        let mut batch = Writebatch::new();
+        for (key, value) in self.sub_chunks.changes()? {
+            batch.put(key, &value);
+        }
+        self.database.write(WriteOptions::new(), &batch).map_err(Error::LevelDb)?;
+        self.sub_chunks.mark_written();
+        Ok(())
+    }
+}
+
+/// The chunks of one dimension of a world.
+#[cfg(feature = "leveldb")]
+pub struct WorldDimension<'a> {
+    database: &'a Database<DbKey>,
+    dimension: Dimension,
+}
+
+#[cfg(feature = "leveldb")]
+impl<'a> WorldDimension<'a> {
+    pub fn dimension(&self) -> Dimension {
+        self.dimension
+    }
+
+    pub fn y_range(&self) -> Range<i32> {
+        self.dimension.y_range()
+    }
+
+    fn chunk_key(&self, x: i32, z: i32, record: ChunkRecord) -> DbKey {
+        DbKey::Chunk(ChunkKey { x, z, dimension: self.dimension, record })
+    }
+
+    /// Iterates over the positions of the chunks of this dimension, found by
+    /// the version record every chunk has.
+    pub fn chunks(&self) -> impl Iterator<Item = (i32, i32)> + 'a {
+        let dimension = self.dimension;
+        db::keys(self.database).filter_map(move |key| match key {
+            DbKey::Chunk(ChunkKey { x, z, dimension: key_dimension, record: ChunkRecord::Version | ChunkRecord::LegacyVersion })
+                if key_dimension == dimension => Some((x, z)),
+            _ => None,
+        })
+    }
+
+    pub fn contains_chunk(&self, x: i32, z: i32) -> Result<bool> {
+        for record in [ChunkRecord::Version, ChunkRecord::LegacyVersion] {
+            if db::get(self.database, &self.chunk_key(x, z, record))?.is_some() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Reads a subchunk of a chunk, where `y_index` is the subchunk's Y
+    /// coordinate divided by 16.
+    pub fn sub_chunk(&self, x: i32, z: i32, y_index: i8) -> Result<Option<SubChunk>> {
+        db::get(self.database, &self.chunk_key(x, z, ChunkRecord::SubChunkPrefix(y_index)))?
+            .map(|value| SubChunk::parse(&mut Cursor::new(value)))
+            .transpose()
+    }
+
+    /// Reads the heightmap and biomes of a chunk from its `Data3D` record, or
+    /// from its `Data2D` record in legacy worlds.
+    pub fn column_meta(&self, x: i32, z: i32) -> Result<Option<ChunkColumnMeta>> {
+        if let Some(value) = db::get(self.database, &self.chunk_key(x, z, ChunkRecord::Data3D))? {
+            return ChunkColumnMeta::parse_data3d(&mut Cursor::new(value), self.y_range()).map(Some);
+        }
+        db::get(self.database, &self.chunk_key(x, z, ChunkRecord::Data2D))?
+            .map(|value| ChunkColumnMeta::parse_data2d(&mut Cursor::new(value)))
+            .transpose()
+    }
+
+    /// Reads the block entities of a chunk, with the errors of the entries
+    /// that were skipped as invalid.
+    pub fn block_entities(&self, x: i32, z: i32) -> Result<(Vec<BlockEntity>, Vec<Error>)> {
+        db::block_entities(self.database, x, z, self.dimension)
+    }
+
+    pub fn entities(&self, x: i32, z: i32) -> Result<Vec<Entity>> {
+        db::chunk_entities(self.database, x, z, self.dimension)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::block::BlockState;
+    use crate::chunk::subchunk::SUB_CHUNK_VOLUME;
+
+    fn block_state(name: &str) -> Tag {
+        BlockState::new(name).to_tag()
+    }
+
+    #[test]
+    fn positions() {
+        let ((dimension, chunk_x, chunk_z, y_index), block) = sub_chunk_position(Dimension::Overworld, -1, -64, 33).unwrap();
+        assert_eq!((dimension, chunk_x, chunk_z, y_index), (Dimension::Overworld, -1, 2, -4));
+        assert_eq!(block, [15, 0, 1]);
+
+        let (position, block) = sub_chunk_position(Dimension::Nether, 16, 127, -16).unwrap();
+        assert_eq!((position, block), ((Dimension::Nether, 1, -1, 7), [0, 15, 0]));
+
+        assert!(matches!(sub_chunk_position(Dimension::Overworld, 0, 320, 0), Err(Error::InvalidInput(_))));
+        assert!(matches!(sub_chunk_position(Dimension::Nether, 0, -1, 0), Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn get_and_set_block() {
+        let stored = SubChunk::air(4);
+        let loads = Cell::new(0);
+        let load = |position: SubChunkPosition| {
+            loads.set(loads.get() + 1);
+            assert_eq!(position, (Dimension::Overworld, 0, 0, 4));
+            Ok(Some(stored.clone()))
+        };
+        let mut cache = SubChunkCache::new();
+        let position = (Dimension::Overworld, 0, 0, 4);
+
+        assert_eq!(cache.get_block(position, [1, 2, 3], load).unwrap(), Some(&block_state("minecraft:air")));
+        cache.set_block(position, [1, 2, 3], block_state("minecraft:stone"), load, |_, _| panic!("subchunk exists")).unwrap();
+        cache.set_block(position, [3, 2, 1], block_state("minecraft:stone"), load, |_, _| panic!("subchunk exists")).unwrap();
+        assert_eq!(cache.get_block(position, [1, 2, 3], load).unwrap(), Some(&block_state("minecraft:stone")));
+        assert_eq!(loads.get(), 1);
+
+        let changes = cache.changes().unwrap();
+        assert_eq!(changes.len(), 1);
+        let sub_chunk = SubChunk::parse(&mut Cursor::new(&changes[0].1)).unwrap();
+        assert_eq!(sub_chunk.layers[0].palette, vec![block_state("minecraft:air"), block_state("minecraft:stone")]);
+        assert_eq!(sub_chunk.block(3, 2, 1), Some(&block_state("minecraft:stone")));
+        assert_eq!(sub_chunk.block(0, 0, 0), Some(&block_state("minecraft:air")));
+
+        cache.mark_written();
+        assert!(cache.changes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn set_block_new_sub_chunk() {
+        let mut cache = SubChunkCache::new();
+        let (position, block) = sub_chunk_position(Dimension::Overworld, -20, -50, 5).unwrap();
+
+        let result = cache.set_block(position, block, block_state("minecraft:stone"), |_| Ok(None), |_, _| Ok(false));
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+        assert!(cache.changes().unwrap().is_empty());
+        assert_eq!(cache.get_block(position, block, |_| panic!("subchunk is cached")).unwrap(), None);
+
+        let contains_chunk = |x, z| {
+            assert_eq!((x, z), (-2, 0));
+            Ok(true)
+        };
+        cache.set_block(position, block, block_state("minecraft:stone"), |_| Ok(None), contains_chunk).unwrap();
+
+        let changes = cache.changes().unwrap();
+        assert_eq!(changes.len(), 1);
+        let (key, value) = &changes[0];
+        assert_eq!(*key, DbKey::Chunk(ChunkKey { x: -2, z: 0, dimension: Dimension::Overworld, record: ChunkRecord::SubChunkPrefix(-4) }));
+        assert_eq!(&value[..3], &[9, 1, -4i8 as u8]);
+
+        let sub_chunk = SubChunk::parse(&mut Cursor::new(value)).unwrap();
+        assert_eq!((sub_chunk.version, sub_chunk.y_index), (9, Some(-4)));
+        assert_eq!(sub_chunk.block(12, 14, 5), Some(&block_state("minecraft:stone")));
+        assert_eq!(sub_chunk.layers[0].indices.iter().filter(|&&index| index == 0).count(), SUB_CHUNK_VOLUME - 1);
+    }
+
+    #[test]
+    fn changes_per_sub_chunk() {
+        let mut cache = SubChunkCache::new();
+        for (dimension, x, y, z) in [(Dimension::Overworld, 0, 0, 0), (Dimension::Overworld, 0, 16, 0), (Dimension::End, 0, 0, 0), (Dimension::End, 1, 2, 3)] {
+            let (position, block) = sub_chunk_position(dimension, x, y, z).unwrap();
+            cache.set_block(position, block, block_state("minecraft:dirt"), |_| Ok(None), |_, _| Ok(true)).unwrap();
+        }
+        let (position, block) = sub_chunk_position(Dimension::Nether, 0, 0, 0).unwrap();
+        cache.get_block(position, block, |_| Ok(Some(SubChunk::air(0)))).unwrap();
+
+        let mut keys: Vec<_> = cache.changes().unwrap().into_iter().map(|(key, _)| key).collect();
+        keys.sort_by_key(|key| key.to_bytes());
+        let sub_chunk_key = |dimension, y_index| DbKey::Chunk(ChunkKey { x: 0, z: 0, dimension, record: ChunkRecord::SubChunkPrefix(y_index) });
+        let mut expected = vec![sub_chunk_key(Dimension::Overworld, 0), sub_chunk_key(Dimension::Overworld, 1), sub_chunk_key(Dimension::End, 0)];
+        expected.sort_by_key(|key| key.to_bytes());
+        assert_eq!(keys, expected);
+    }
+}